@@ -0,0 +1,202 @@
+use std::{collections::VecDeque, ops::Range};
+
+use crate::{trie::Trie, RefCounter};
+
+// Aho–Corasick automaton over the keys of a trie. Every element of every key
+// gets a state, and each state has a failure link to the state of its
+// longest proper suffix that is also a prefix of some key, so a scan never
+// moves back in the haystack
+pub struct Matcher<T, U> {
+    states: Vec<State<T, U>>,
+}
+
+struct State<T, U> {
+    transitions: Vec<(T, usize)>,
+    fail: usize,
+    // Closest state along the failure links that ends a key
+    output_link: Option<usize>,
+    depth: usize,
+    values: Vec<RefCounter<U>>,
+}
+
+const ROOT: usize = 0;
+
+impl<T: PartialEq + Clone, U> Matcher<T, U> {
+    // The empty key is left out, as it would match at every position
+    pub(crate) fn from_trie(trie: &Trie<T, U>) -> Self {
+        let mut matcher = Matcher {
+            states: vec![State::new(0)],
+        };
+        matcher.add_children(ROOT, trie);
+        matcher.link_failures();
+        matcher
+    }
+    fn add_children(&mut self, from: usize, node: &Trie<T, U>) {
+        for (label, child) in &node.adjecent_nodes {
+            let mut state = from;
+            for element in label.iter() {
+                let next = self.states.len();
+                self.states.push(State::new(self.states[state].depth + 1));
+                self.states[state].transitions.push((element.clone(), next));
+                state = next;
+            }
+            self.states[state].values = child.stored_value.clone();
+            self.add_children(state, child);
+        }
+    }
+    // Breadth first, so the failure of every shallower state is known
+    fn link_failures(&mut self) {
+        let mut queue: VecDeque<usize> = self.states[ROOT]
+            .transitions
+            .iter()
+            .map(|(_, next)| *next)
+            .collect();
+        while let Some(state) = queue.pop_front() {
+            for index in 0..self.states[state].transitions.len() {
+                let (element, next) = self.states[state].transitions[index].clone();
+                let mut fallback = self.states[state].fail;
+                let fail = loop {
+                    if let Some(target) = self.transition(fallback, &element) {
+                        break target;
+                    }
+                    if fallback == ROOT {
+                        break ROOT;
+                    }
+                    fallback = self.states[fallback].fail;
+                };
+                let output_link = if self.states[fail].values.is_empty() {
+                    self.states[fail].output_link
+                } else {
+                    Some(fail)
+                };
+                self.states[next].fail = fail;
+                self.states[next].output_link = output_link;
+                queue.push_back(next);
+            }
+        }
+    }
+    fn transition(&self, state: usize, element: &T) -> Option<usize> {
+        self.states[state]
+            .transitions
+            .iter()
+            .find(|(expected, _)| expected == element)
+            .map(|(_, next)| *next)
+    }
+    fn step(&self, mut state: usize, element: &T) -> usize {
+        loop {
+            if let Some(next) = self.transition(state, element) {
+                return next;
+            }
+            if state == ROOT {
+                return ROOT;
+            }
+            state = self.states[state].fail;
+        }
+    }
+    // Every occurrence of every key in the haystack, overlapping ones
+    // included. Occurrences come out by end position, longest first
+    pub fn find_all<'a, 'h>(&'a self, haystack: &'h [T]) -> MatchIterator<'a, 'h, T, U> {
+        MatchIterator {
+            matcher: self,
+            haystack,
+            position: 0,
+            state: ROOT,
+            reporting: None,
+        }
+    }
+    pub fn is_match(&self, haystack: &[T]) -> bool {
+        self.find_all(haystack).next().is_some()
+    }
+}
+
+impl<T, U> State<T, U> {
+    fn new(depth: usize) -> Self {
+        State {
+            transitions: Vec::new(),
+            fail: ROOT,
+            output_link: None,
+            depth,
+            values: Vec::new(),
+        }
+    }
+}
+
+pub struct MatchIterator<'a, 'h, T, U> {
+    matcher: &'a Matcher<T, U>,
+    haystack: &'h [T],
+    position: usize,
+    state: usize,
+    // State whose values are being reported at the current position, and
+    // the next value to report
+    reporting: Option<(usize, usize)>,
+}
+
+impl<'a, 'h, T: PartialEq + Clone, U> Iterator for MatchIterator<'a, 'h, T, U> {
+    type Item = (Range<usize>, &'a U);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((state, index)) = self.reporting {
+                let found = &self.matcher.states[state];
+                if let Some(value) = found.values.get(index) {
+                    self.reporting = Some((state, index + 1));
+                    let start = self.position - found.depth;
+                    return Some((start..self.position, value.as_ref()));
+                }
+                self.reporting = found.output_link.map(|state| (state, 0));
+                continue;
+            }
+            let element = self.haystack.get(self.position)?;
+            self.state = self.matcher.step(self.state, element);
+            self.position += 1;
+            let current = &self.matcher.states[self.state];
+            self.reporting = if current.values.is_empty() {
+                current.output_link.map(|state| (state, 0))
+            } else {
+                Some((self.state, 0))
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::trie::Trie;
+
+    #[test]
+    fn test_aho_corasick_overlapping_matches() {
+        let keywords = Trie::empty_store()
+            .insert_store("he", "he")
+            .insert_store("she", "she")
+            .insert_store("his", "his")
+            .insert_store("hers", "hers");
+        let matcher = keywords.build_matcher();
+        let found: Vec<_> = matcher.find_all(b"ushers").map(|(r, v)| (r, *v)).collect();
+        assert_eq!(found, vec![(1..4, "she"), (2..4, "he"), (2..6, "hers")]);
+        assert!(matcher.find_all(b"xyz").next().is_none());
+        assert!(matcher.is_match(b"this"));
+        assert!(!matcher.is_match(b""));
+    }
+
+    #[test]
+    fn test_aho_corasick_matches_search() {
+        let words = ["a", "ab", "bab", "bc", "bca", "c", "caa"];
+        let trie = words
+            .iter()
+            .fold(Trie::empty_store(), |t, word| t.insert_store(word, *word))
+            .insert_store("", "empty");
+        let matcher = trie.build_matcher();
+        let haystack = b"abccab";
+        let mut found: Vec<_> = matcher.find_all(haystack).collect();
+        found.sort_by_key(|(range, _)| (range.start, range.end));
+        let mut expected = Vec::new();
+        for start in 0..haystack.len() {
+            for end in start + 1..=haystack.len() {
+                if let Some(values) = trie.get_store(&haystack[start..end]) {
+                    expected.push((start..end, values[0]));
+                }
+            }
+        }
+        assert_eq!(found, expected);
+    }
+}
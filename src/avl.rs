@@ -1,21 +1,114 @@
-use std::cmp::max;
+use std::{
+    borrow::Borrow,
+    cmp::{max, Ordering},
+    collections::BTreeMap,
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    iter::Peekable,
+    marker::PhantomData,
+    ops::{Bound, Index, RangeBounds},
+};
 
-use crate::RefCounter;
+use crate::{frozen::FrozenMap, RefCounter};
 
-pub enum AVL<K, V = ()> {
+#[derive(Default)]
+pub enum AVL<K, V = (), C = Natural, S = ()> {
+    #[default]
     Empty,
     Node {
         key: RefCounter<K>,
         value: RefCounter<V>,
-        left: RefCounter<AVL<K, V>>,
-        right: RefCounter<AVL<K, V>>,
+        left: RefCounter<AVL<K, V, C, S>>,
+        right: RefCounter<AVL<K, V, C, S>>,
+        size: usize,
+        height: i64,
+        summary: S,
+        ordering: PhantomData<C>,
     },
 }
 
-pub type OrderedMap<K, V> = AVL<K, V>;
-pub type OrderedSet<K> = AVL<K>;
+pub trait Compare<K: ?Sized> {
+    fn compare(a: &K, b: &K) -> Ordering;
+}
+
+// Orders keys by their Ord implementation
+pub struct Natural;
+
+impl<K: Ord + ?Sized> Compare<K> for Natural {
+    fn compare(a: &K, b: &K) -> Ordering {
+        a.cmp(b)
+    }
+}
+
+// Value cached on every node and rebuilt from its entry and the summaries of
+// its children, e.g. the largest end point below an interval tree node
+pub trait Summary<K, V>: Clone {
+    fn summarize(
+        key: &RefCounter<K>,
+        value: &RefCounter<V>,
+        left: Option<&Self>,
+        right: Option<&Self>,
+    ) -> Self;
+}
+
+impl<K, V> Summary<K, V> for () {
+    fn summarize(_: &RefCounter<K>, _: &RefCounter<V>, _: Option<&()>, _: Option<&()>) {}
+}
+
+// Flips the order given by another comparator. Comparators are types, not
+// values, so this is only ever named, never built
+pub struct Reverse<C = Natural>(PhantomData<C>);
+
+impl<K: ?Sized, C: Compare<K>> Compare<K> for Reverse<C> {
+    fn compare(a: &K, b: &K) -> Ordering {
+        C::compare(b, a)
+    }
+}
+
+// Invariant broken by the node at the given in-order position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvariantViolation {
+    Unordered { index: usize },
+    Unbalanced { index: usize },
+    WrongSize { index: usize },
+    WrongHeight { index: usize },
+}
+
+impl std::fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InvariantViolation::Unordered { index } => {
+                write!(f, "key at index {} is out of order", index)
+            }
+            InvariantViolation::Unbalanced { index } => {
+                write!(f, "node at index {} is unbalanced", index)
+            }
+            InvariantViolation::WrongSize { index } => {
+                write!(f, "node at index {} has a wrong cached size", index)
+            }
+            InvariantViolation::WrongHeight { index } => {
+                write!(f, "node at index {} has a wrong cached height", index)
+            }
+        }
+    }
+}
 
-impl<K, V> Clone for AVL<K, V> {
+impl std::error::Error for InvariantViolation {}
+
+pub type OrderedMap<K, V, C = Natural> = AVL<K, V, C>;
+
+// Kept so imports from before OrderedSet got its own module still resolve
+pub use crate::ordered_set::OrderedSet;
+
+type Split<K, V, C, S> = (AVL<K, V, C, S>, Option<RefCounter<V>>, AVL<K, V, C, S>);
+type Path<'a, K, V, C, S> = Vec<(&'a AVL<K, V, C, S>, Ordering)>;
+type PendingEntry<K, V, C, S> = (RefCounter<K>, RefCounter<V>, AVL<K, V, C, S>);
+type Located<'a, K, V, C, S> = (Path<'a, K, V, C, S>, &'a AVL<K, V, C, S>);
+type Removed<K, V, C, S> = (RefCounter<V>, AVL<K, V, C, S>);
+type Popped<'a, K, V, C, S> = (&'a K, &'a V, AVL<K, V, C, S>);
+type Halves<K, V, C, S> = (AVL<K, V, C, S>, AVL<K, V, C, S>);
+
+impl<K, V, C, S: Clone> Clone for AVL<K, V, C, S> {
     fn clone(&self) -> Self {
         match self {
             Self::Empty => Self::Empty,
@@ -24,304 +117,1926 @@ impl<K, V> Clone for AVL<K, V> {
                 value,
                 left,
                 right,
+                size,
+                height,
+                summary,
+                ..
             } => Self::Node {
                 key: key.clone(),
                 value: value.clone(),
                 left: left.clone(),
                 right: right.clone(),
+                size: *size,
+                height: *height,
+                summary: summary.clone(),
+                ordering: PhantomData,
             },
         }
     }
 }
 
-impl<K: Ord> AVL<K> {
+impl<K, V, C, S> Drop for AVL<K, V, C, S> {
+    fn drop(&mut self) {
+        // Tear down uniquely owned subtrees with an explicit stack, so dropping
+        // a large tree never recurses once per level
+        let mut stack = Vec::new();
+        self.detach_unique_children(&mut stack);
+        while let Some(mut node) = stack.pop() {
+            node.detach_unique_children(&mut stack);
+        }
+    }
+}
+
+impl<K, Q: ?Sized, V, C, S: Summary<K, V>> Index<&Q> for AVL<K, V, C, S>
+where
+    K: Borrow<Q>,
+    C: Compare<K> + Compare<Q>,
+{
+    type Output = V;
+
+    fn index(&self, key: &Q) -> &V {
+        self.find(key).expect("no entry found for key")
+    }
+}
+
+impl<K: Debug, V: Debug, C, S> Debug for AVL<K, V, C, S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+// In-order stream of a tree for comparisons. Subtrees stay whole until the
+// other side needs them split, so shared ones can be skipped at once
+enum Piece<'a, K, V, C, S> {
+    Tree(&'a AVL<K, V, C, S>),
+    Entry(&'a K, &'a V),
+}
+
+impl<'a, K, V, C, S> Piece<'a, K, V, C, S> {
+    // Pushes the parts of a non-empty tree so the leftmost is popped first
+    fn split_onto(tree: &'a AVL<K, V, C, S>, stack: &mut Vec<Piece<'a, K, V, C, S>>) {
+        if let AVL::Node {
+            key,
+            value,
+            left,
+            right,
+            ..
+        } = tree
+        {
+            if !right.is_empty() {
+                stack.push(Piece::Tree(right));
+            }
+            stack.push(Piece::Entry(key, value));
+            if !left.is_empty() {
+                stack.push(Piece::Tree(left));
+            }
+        }
+    }
+}
+
+// Walks both trees in step, skipping subtrees that are shared between them
+// and comparing entries only where their shapes differ
+impl<K: PartialEq, V: PartialEq, C, S> PartialEq for AVL<K, V, C, S> {
+    fn eq(&self, other: &Self) -> bool {
+        if self.len() != other.len() {
+            return false;
+        }
+        let mut left = Vec::new();
+        let mut right = Vec::new();
+        if !self.is_empty() {
+            left.push(Piece::Tree(self));
+            right.push(Piece::Tree(other));
+        }
+        loop {
+            match (left.pop(), right.pop()) {
+                (None, None) => return true,
+                (Some(Piece::Tree(a)), Some(Piece::Tree(b))) => {
+                    if std::ptr::eq(a, b) {
+                        continue;
+                    }
+                    // Only the bigger one is split, as two trees of different
+                    // sizes can never be the same cells
+                    match a.len().cmp(&b.len()) {
+                        Ordering::Greater => {
+                            Piece::split_onto(a, &mut left);
+                            right.push(Piece::Tree(b));
+                        }
+                        Ordering::Less => {
+                            left.push(Piece::Tree(a));
+                            Piece::split_onto(b, &mut right);
+                        }
+                        Ordering::Equal => {
+                            Piece::split_onto(a, &mut left);
+                            Piece::split_onto(b, &mut right);
+                        }
+                    }
+                }
+                (Some(Piece::Tree(a)), Some(entry)) => {
+                    Piece::split_onto(a, &mut left);
+                    right.push(entry);
+                }
+                (Some(entry), Some(Piece::Tree(b))) => {
+                    left.push(entry);
+                    Piece::split_onto(b, &mut right);
+                }
+                (Some(Piece::Entry(k1, v1)), Some(Piece::Entry(k2, v2))) => {
+                    let same =
+                        (std::ptr::eq(k1, k2) || k1 == k2) && (std::ptr::eq(v1, v2) || v1 == v2);
+                    if !same {
+                        return false;
+                    }
+                }
+                _ => return false,
+            }
+        }
+    }
+}
+
+impl<K: Eq, V: Eq, C, S> Eq for AVL<K, V, C, S> {}
+
+impl<K: Hash, V: Hash, C, S> Hash for AVL<K, V, C, S> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len());
+        for entry in self.iter() {
+            entry.hash(state);
+        }
+    }
+}
+
+impl<K, C: Compare<K>, S: Summary<K, ()>> AVL<K, (), C, S> {
     pub fn insert(&self, value: K) -> Self {
-        self.put(value, ())
+        self.put_if_changed(value, ()).0
     }
-    pub fn search(&self, value: &K) -> bool {
+    pub fn search<Q: ?Sized>(&self, value: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
         self.find(value).is_some()
     }
+    pub fn insert_owned(self, value: K) -> Self {
+        self.put_owned(value, ())
+    }
 }
 
-impl<K: Ord, V> AVL<K, V> {
-    pub fn empty() -> AVL<K, V> {
-        return AVL::Empty;
+impl<K, V, C, S> AVL<K, V, C, S> {
+    fn node(
+        key: RefCounter<K>,
+        value: RefCounter<V>,
+        left: RefCounter<AVL<K, V, C, S>>,
+        right: RefCounter<AVL<K, V, C, S>>,
+    ) -> AVL<K, V, C, S>
+    where
+        S: Summary<K, V>,
+    {
+        let size = 1 + left.len() + right.len();
+        let height = 1 + max(left.height(), right.height());
+        let summary = S::summarize(&key, &value, left.summary(), right.summary());
+        AVL::Node {
+            key,
+            value,
+            left,
+            right,
+            size,
+            height,
+            summary,
+            ordering: PhantomData,
+        }
     }
-    pub fn is_empty(&self) -> bool {
+    // Recomputes the cached fields after a child was replaced in place
+    fn refresh(&mut self)
+    where
+        S: Summary<K, V>,
+    {
+        if let AVL::Node {
+            key,
+            value,
+            left,
+            right,
+            size,
+            height,
+            summary,
+            ..
+        } = self
+        {
+            *size = 1 + left.len() + right.len();
+            *height = 1 + max(left.height(), right.height());
+            *summary = S::summarize(key, value, left.summary(), right.summary());
+        }
+    }
+    pub fn len(&self) -> usize {
         match self {
-            AVL::Empty => true,
-            _ => false,
+            AVL::Empty => 0,
+            AVL::Node { size, .. } => *size,
         }
     }
+    pub fn is_empty(&self) -> bool {
+        matches!(self, AVL::Empty)
+    }
     fn height(&self) -> i64 {
         match self {
             AVL::Empty => 0,
-            AVL::Node {
-                key: _,
-                value: _,
-                left,
-                right,
-            } => 1 + max(&left.height(), &right.height()),
+            AVL::Node { height, .. } => *height,
         }
     }
-    fn diff(&self) -> i64 {
+    pub fn summary(&self) -> Option<&S> {
         match self {
-            AVL::Empty => 0,
-            AVL::Node {
-                key: _,
-                value: _,
-                left,
-                right,
-            } => &left.height() - &right.height(),
+            AVL::Empty => None,
+            AVL::Node { summary, .. } => Some(summary),
+        }
+    }
+}
+
+impl<K, V, C, S> AVL<K, V, C, S> {
+    fn detach_unique_children(&mut self, stack: &mut Vec<AVL<K, V, C, S>>) {
+        if let AVL::Node { left, right, .. } = self {
+            for child in [left, right] {
+                if let Some(subtree) = RefCounter::get_mut(child) {
+                    if !subtree.is_empty() {
+                        stack.push(std::mem::replace(subtree, AVL::Empty));
+                    }
+                }
+            }
         }
     }
-    pub fn find(&self, target_value: &K) -> Option<&V> {
+    pub fn iter(&self) -> AVLIterator<'_, K, V, C, S> {
+        let mut iter = AVLIterator {
+            front: Vec::new(),
+            back: Vec::new(),
+            remaining: self.len(),
+        };
+        iter.push_left_spine(self);
+        iter.push_right_spine(self);
+        iter
+    }
+    pub fn keys(&self) -> KeysIterator<'_, K, V, C, S> {
+        KeysIterator { inner: self.iter() }
+    }
+    pub fn values(&self) -> ValuesIterator<'_, K, V, C, S> {
+        ValuesIterator { inner: self.iter() }
+    }
+    pub fn map_values<W, F: Fn(&V) -> W>(&self, f: F) -> AVL<K, W, C, S>
+    where
+        S: Summary<K, W>,
+    {
+        self.map_values_with(&f)
+    }
+    fn map_values_with<W, F: Fn(&V) -> W>(&self, f: &F) -> AVL<K, W, C, S>
+    where
+        S: Summary<K, W>,
+    {
         match self {
-            AVL::Empty => Option::None,
+            AVL::Empty => AVL::Empty,
             AVL::Node {
                 key,
                 value,
                 left,
                 right,
-            } => match target_value.cmp(key) {
-                std::cmp::Ordering::Less => left.find(target_value),
-                std::cmp::Ordering::Equal => Option::Some(value.as_ref()),
-                std::cmp::Ordering::Greater => right.find(target_value),
-            },
+                ..
+            } => AVL::node(
+                key.clone(),
+                RefCounter::new(f(value)),
+                RefCounter::new(left.map_values_with(f)),
+                RefCounter::new(right.map_values_with(f)),
+            ),
         }
     }
-    fn right_rotation(&self) -> AVL<K, V> {
-        if let AVL::Node {
-            key: x,
-            value: vx,
-            left: lt,
-            right: t3,
-        } = self
-        {
-            if let AVL::Node {
-                key: y,
-                value: vy,
-                left: t1,
-                right: t2,
-            } = (*lt).as_ref()
-            {
-                return AVL::Node {
-                    key: y.clone(),
-                    left: t1.clone(),
-                    value: vy.clone(),
-                    right: RefCounter::new(AVL::Node {
-                        key: x.clone(),
-                        value: vx.clone(),
-                        left: t2.clone(),
-                        right: t3.clone(),
-                    }),
-                };
+}
+
+// Both ends keep their own stack; `remaining` stops them once they meet
+pub struct AVLIterator<'a, K, V, C = Natural, S = ()> {
+    front: Vec<&'a AVL<K, V, C, S>>,
+    back: Vec<&'a AVL<K, V, C, S>>,
+    remaining: usize,
+}
+
+impl<'a, K, V, C, S> AVLIterator<'a, K, V, C, S> {
+    fn push_left_spine(&mut self, mut node: &'a AVL<K, V, C, S>) {
+        while let AVL::Node { left, .. } = node {
+            self.front.push(node);
+            node = left;
+        }
+    }
+    fn push_right_spine(&mut self, mut node: &'a AVL<K, V, C, S>) {
+        while let AVL::Node { right, .. } = node {
+            self.back.push(node);
+            node = right;
+        }
+    }
+}
+
+impl<'a, K, V, C, S> Iterator for AVLIterator<'a, K, V, C, S> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        match self.front.pop()? {
+            AVL::Empty => None,
+            AVL::Node {
+                key, value, right, ..
+            } => {
+                self.remaining -= 1;
+                self.push_left_spine(right);
+                Some((key.as_ref(), value.as_ref()))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K, V, C, S> DoubleEndedIterator for AVLIterator<'a, K, V, C, S> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        match self.back.pop()? {
+            AVL::Empty => None,
+            AVL::Node {
+                key, value, left, ..
+            } => {
+                self.remaining -= 1;
+                self.push_right_spine(left);
+                Some((key.as_ref(), value.as_ref()))
             }
         }
-        return self.clone();
     }
-    fn right_fix(&self) -> AVL<K, V> {
+}
+
+impl<'a, K, V, C, S> ExactSizeIterator for AVLIterator<'a, K, V, C, S> {}
+
+pub struct KeysIterator<'a, K, V, C = Natural, S = ()> {
+    inner: AVLIterator<'a, K, V, C, S>,
+}
+
+impl<'a, K, V, C, S> Iterator for KeysIterator<'a, K, V, C, S> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V, C, S> DoubleEndedIterator for KeysIterator<'a, K, V, C, S> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(key, _)| key)
+    }
+}
+
+impl<'a, K, V, C, S> ExactSizeIterator for KeysIterator<'a, K, V, C, S> {}
+
+pub struct ValuesIterator<'a, K, V, C = Natural, S = ()> {
+    inner: AVLIterator<'a, K, V, C, S>,
+}
+
+impl<'a, K, V, C, S> Iterator for ValuesIterator<'a, K, V, C, S> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V, C, S> DoubleEndedIterator for ValuesIterator<'a, K, V, C, S> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(_, value)| value)
+    }
+}
+
+impl<'a, K, V, C, S> ExactSizeIterator for ValuesIterator<'a, K, V, C, S> {}
+
+#[derive(Debug, PartialEq)]
+pub enum DiffEntry<'a, K, V> {
+    Added(&'a K, &'a V),
+    Removed(&'a K, &'a V),
+    Changed(&'a K, &'a V, &'a V),
+}
+
+enum DiffItem<'a, K, V, C, S> {
+    Tree(&'a AVL<K, V, C, S>),
+    Entry(&'a K, &'a V),
+}
+
+// Walks both trees in order, skipping any subtree the two versions share
+pub struct DiffIterator<'a, K, V, C = Natural, S = ()> {
+    old: Vec<DiffItem<'a, K, V, C, S>>,
+    new: Vec<DiffItem<'a, K, V, C, S>>,
+}
+
+impl<'a, K, V, C, S> DiffIterator<'a, K, V, C, S> {
+    fn push_tree(stack: &mut Vec<DiffItem<'a, K, V, C, S>>, tree: &'a AVL<K, V, C, S>) {
+        if !tree.is_empty() {
+            stack.push(DiffItem::Tree(tree));
+        }
+    }
+    fn expand(stack: &mut Vec<DiffItem<'a, K, V, C, S>>, tree: &'a AVL<K, V, C, S>) {
         if let AVL::Node {
-            key: x,
-            value: vx,
-            left: t1,
-            right: t2,
-        } = self
+            key,
+            value,
+            left,
+            right,
+            ..
+        } = tree
         {
-            if t1.diff() == -1 {
-                return AVL::Node {
-                    key: x.clone(),
-                    value: vx.clone(),
-                    left: RefCounter::new(t1.left_rotation()),
-                    right: t2.clone(),
+            Self::push_tree(stack, right);
+            stack.push(DiffItem::Entry(key, value));
+            Self::push_tree(stack, left);
+        }
+    }
+}
+
+impl<'a, K, V: PartialEq, C: Compare<K>, S> Iterator for DiffIterator<'a, K, V, C, S> {
+    type Item = DiffEntry<'a, K, V>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match (self.old.pop(), self.new.pop()) {
+                (None, None) => return None,
+                (Some(DiffItem::Tree(old)), None) => Self::expand(&mut self.old, old),
+                (None, Some(DiffItem::Tree(new))) => Self::expand(&mut self.new, new),
+                (Some(DiffItem::Entry(key, value)), None) => {
+                    return Some(DiffEntry::Removed(key, value))
+                }
+                (None, Some(DiffItem::Entry(key, value))) => {
+                    return Some(DiffEntry::Added(key, value))
+                }
+                (Some(DiffItem::Tree(old)), Some(DiffItem::Tree(new))) => {
+                    if std::ptr::eq(old, new) {
+                        continue;
+                    }
+                    // A shared subtree can only hide inside the taller one
+                    if old.height() >= new.height() {
+                        Self::expand(&mut self.old, old);
+                        self.new.push(DiffItem::Tree(new));
+                    } else {
+                        self.old.push(DiffItem::Tree(old));
+                        Self::expand(&mut self.new, new);
+                    }
+                }
+                (Some(DiffItem::Tree(old)), Some(entry)) => {
+                    Self::expand(&mut self.old, old);
+                    self.new.push(entry);
+                }
+                (Some(entry), Some(DiffItem::Tree(new))) => {
+                    self.old.push(entry);
+                    Self::expand(&mut self.new, new);
+                }
+                (
+                    Some(DiffItem::Entry(old_key, old_value)),
+                    Some(DiffItem::Entry(new_key, new_value)),
+                ) => match C::compare(old_key, new_key) {
+                    Ordering::Less => {
+                        self.new.push(DiffItem::Entry(new_key, new_value));
+                        return Some(DiffEntry::Removed(old_key, old_value));
+                    }
+                    Ordering::Greater => {
+                        self.old.push(DiffItem::Entry(old_key, old_value));
+                        return Some(DiffEntry::Added(new_key, new_value));
+                    }
+                    Ordering::Equal => {
+                        if !std::ptr::eq(old_value, new_value) && old_value != new_value {
+                            return Some(DiffEntry::Changed(new_key, old_value, new_value));
+                        }
+                    }
+                },
+            }
+        }
+    }
+}
+
+// Ascending walk over two trees at once. Entries of both trees are yielded,
+// and the tie-break decides which comes first when the keys are equal
+pub struct MergeIterator<'a, K, V, F, C = Natural, S = ()> {
+    left: Peekable<AVLIterator<'a, K, V, C, S>>,
+    right: Peekable<AVLIterator<'a, K, V, C, S>>,
+    tie_break: F,
+}
+
+impl<'a, K, V, F, C, S> Iterator for MergeIterator<'a, K, V, F, C, S>
+where
+    F: FnMut(&V, &V) -> Ordering,
+    C: Compare<K>,
+{
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let take_left = match (self.left.peek(), self.right.peek()) {
+            (None, None) => return None,
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (Some((left_key, left_value)), Some((right_key, right_value))) => {
+                match C::compare(left_key, right_key) {
+                    Ordering::Less => true,
+                    Ordering::Greater => false,
+                    Ordering::Equal => {
+                        (self.tie_break)(left_value, right_value) != Ordering::Greater
+                    }
+                }
+            }
+        };
+        if take_left {
+            self.left.next()
+        } else {
+            self.right.next()
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.left.len() + self.right.len();
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a, K, V, F, C, S> ExactSizeIterator for MergeIterator<'a, K, V, F, C, S>
+where
+    F: FnMut(&V, &V) -> Ordering,
+    C: Compare<K>,
+{
+}
+
+// Points at an entry of a tree, or at the empty position past its last
+// entry (which is also right before its first one)
+pub struct Cursor<'a, K, V, C = Natural, S = ()> {
+    root: &'a AVL<K, V, C, S>,
+    path: Path<'a, K, V, C, S>,
+    current: Option<&'a AVL<K, V, C, S>>,
+}
+
+impl<'a, K, V, C, S> Clone for Cursor<'a, K, V, C, S> {
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root,
+            path: self.path.clone(),
+            current: self.current,
+        }
+    }
+}
+
+impl<'a, K, V, C: Compare<K>, S: Summary<K, V>> Cursor<'a, K, V, C, S> {
+    pub fn peek(&self) -> Option<(&'a K, &'a V)> {
+        match self.current? {
+            AVL::Empty => None,
+            AVL::Node { key, value, .. } => Some((key.as_ref(), value.as_ref())),
+        }
+    }
+    fn descend(&mut self, mut node: &'a AVL<K, V, C, S>, side: Ordering) {
+        while let AVL::Node { left, right, .. } = node {
+            let next = if side == Ordering::Less { left } else { right };
+            if next.is_empty() {
+                break;
+            }
+            self.path.push((node, side));
+            node = next;
+        }
+        self.current = if node.is_empty() { None } else { Some(node) };
+    }
+    // Moves towards `side` (Less for the previous entry, Greater for the next)
+    fn step(&mut self, side: Ordering) {
+        let opposite = side.reverse();
+        match self.current {
+            None => self.descend(self.root, opposite),
+            Some(AVL::Node { left, right, .. }) => {
+                let child = if side == Ordering::Less { left } else { right };
+                if !child.is_empty() {
+                    self.path.push((self.current.unwrap(), side));
+                    self.descend(child, opposite);
+                    return;
+                }
+                self.current = None;
+                while let Some((parent, taken)) = self.path.pop() {
+                    if taken == opposite {
+                        self.current = Some(parent);
+                        return;
+                    }
+                }
+            }
+            Some(AVL::Empty) => self.current = None,
+        }
+    }
+    // Moving hands back a new cursor and leaves this one where it is
+    pub fn move_next(&self) -> Self {
+        let mut next = self.clone();
+        next.step(Ordering::Greater);
+        next
+    }
+    pub fn move_prev(&self) -> Self {
+        let mut previous = self.clone();
+        previous.step(Ordering::Less);
+        previous
+    }
+    // Returns None if the key does not belong right before the cursor
+    pub fn insert_before(&self, key: K, value: V) -> Option<AVL<K, V, C, S>> {
+        if let Some((next_key, _)) = self.peek() {
+            if C::compare(&key, next_key) != Ordering::Less {
+                return None;
+            }
+        }
+        if let Some((previous_key, _)) = self.move_prev().peek() {
+            if C::compare(previous_key, &key) != Ordering::Less {
+                return None;
+            }
+        }
+        let mut path = self.path.clone();
+        let mut node = self.root;
+        if let Some(current) = self.current {
+            path.push((current, Ordering::Less));
+            if let AVL::Node { left, .. } = current {
+                node = left;
+            }
+        }
+        while let AVL::Node { right, .. } = node {
+            path.push((node, Ordering::Greater));
+            node = right;
+        }
+        let leaf = AVL::node(
+            RefCounter::new(key),
+            RefCounter::new(value),
+            RefCounter::new(AVL::Empty),
+            RefCounter::new(AVL::Empty),
+        );
+        Some(AVL::rebuild_path(path, leaf))
+    }
+    pub fn remove(&self) -> Option<AVL<K, V, C, S>> {
+        let current = self.current?;
+        Some(AVL::rebuild_path(self.path.clone(), current.delete_root()))
+    }
+}
+
+impl<'a, K, V, C, S> IntoIterator for &'a AVL<K, V, C, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = AVLIterator<'a, K, V, C, S>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K: Clone, V: Clone, C, S: Summary<K, V>> IntoIterator for AVL<K, V, C, S> {
+    type Item = (K, V);
+    type IntoIter = AVLIntoIterator<K, V, C, S>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let mut iter = AVLIntoIterator {
+            stack: Vec::new(),
+            remaining: self.len(),
+        };
+        iter.push_left_spine(self);
+        iter
+    }
+}
+
+// Entries are moved out of nodes that are not shared with any other tree,
+// and cloned otherwise
+pub struct AVLIntoIterator<K, V, C = Natural, S = ()> {
+    stack: Vec<PendingEntry<K, V, C, S>>,
+    remaining: usize,
+}
+
+impl<K: Clone, V: Clone, C, S: Summary<K, V>> AVLIntoIterator<K, V, C, S> {
+    fn take_subtree(subtree: &mut RefCounter<AVL<K, V, C, S>>) -> AVL<K, V, C, S> {
+        match RefCounter::get_mut(subtree) {
+            Some(unique) => std::mem::replace(unique, AVL::Empty),
+            None => subtree.as_ref().clone(),
+        }
+    }
+    fn push_left_spine(&mut self, mut node: AVL<K, V, C, S>) {
+        while let AVL::Node {
+            key,
+            value,
+            left,
+            right,
+            ..
+        } = &mut node
+        {
+            let left = Self::take_subtree(left);
+            let right = Self::take_subtree(right);
+            self.stack.push((key.clone(), value.clone(), right));
+            // Dropping the node leaves the pushed cells as the only owners
+            node = left;
+        }
+    }
+}
+
+impl<K: Clone, V: Clone, C, S: Summary<K, V>> Iterator for AVLIntoIterator<K, V, C, S> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, value, right) = self.stack.pop()?;
+        self.remaining -= 1;
+        self.push_left_spine(right);
+        Some((
+            RefCounter::unwrap_or_clone(key),
+            RefCounter::unwrap_or_clone(value),
+        ))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K: Clone, V: Clone, C, S: Summary<K, V>> ExactSizeIterator for AVLIntoIterator<K, V, C, S> {}
+
+impl<K: Ord, V> AVL<K, V> {
+    pub fn empty() -> AVL<K, V> {
+        AVL::Empty
+    }
+    // Builds a balanced tree in linear time. Keys must come in ascending order:
+    // a repeated key keeps its last value, and a key smaller than the one
+    // before it panics. Use collect for input in any order
+    pub fn from_sorted_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> AVL<K, V> {
+        AVL::from_sorted_entries(iter)
+    }
+}
+
+impl<K, V, C: Compare<K>> AVL<K, V, C> {
+    // Empty tree ordered by C, e.g. AVL::<K, V, C>::empty_ordered()
+    pub fn empty_ordered() -> AVL<K, V, C> {
+        AVL::Empty
+    }
+}
+
+impl<K, V, C: Compare<K>, S: Summary<K, V>> AVL<K, V, C, S> {
+    fn balance_factor(&self) -> i64 {
+        match self {
+            AVL::Empty => 0,
+            AVL::Node { left, right, .. } => left.height() - right.height(),
+        }
+    }
+    // Panics on keys out of order, see from_sorted_iter. Frozen maps and
+    // BTreeMaps are always in order, so thawing and converting never do
+    pub(crate) fn from_sorted_entries<I: IntoIterator<Item = (K, V)>>(iter: I) -> AVL<K, V, C, S> {
+        let mut entries: Vec<(K, V)> = Vec::new();
+        for (key, value) in iter {
+            match entries.last_mut() {
+                Some(last) => match C::compare(&last.0, &key) {
+                    // Repeated keys keep the last value, as successive puts would
+                    Ordering::Equal => last.1 = value,
+                    Ordering::Less => entries.push((key, value)),
+                    Ordering::Greater => panic!("from_sorted_iter requires ascending keys"),
+                },
+                None => entries.push((key, value)),
+            }
+        }
+        let len = entries.len();
+        AVL::build_balanced(&mut entries.into_iter(), len)
+    }
+    fn build_balanced<I: Iterator<Item = (K, V)>>(entries: &mut I, len: usize) -> AVL<K, V, C, S> {
+        if len == 0 {
+            return AVL::Empty;
+        }
+        let left = AVL::build_balanced(entries, len / 2);
+        let (key, value) = entries.next().unwrap();
+        let right = AVL::build_balanced(entries, len - len / 2 - 1);
+        AVL::node(
+            RefCounter::new(key),
+            RefCounter::new(value),
+            RefCounter::new(left),
+            RefCounter::new(right),
+        )
+    }
+    // Copies the entries into one sorted array for read-mostly snapshots
+    pub fn freeze(&self) -> FrozenMap<K, V, C>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        FrozenMap::from_sorted_vec(
+            self.iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+        )
+    }
+    pub fn find<Q: ?Sized>(&self, target_value: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.get_key_value(target_value).map(|(_, value)| value)
+    }
+    pub fn get_key_value<Q: ?Sized>(&self, target_key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let mut node = self;
+        while let AVL::Node {
+            key,
+            value,
+            left,
+            right,
+            ..
+        } = node
+        {
+            node = match C::compare(target_key, (**key).borrow()) {
+                Ordering::Less => left,
+                Ordering::Equal => return Option::Some((key.as_ref(), value.as_ref())),
+                Ordering::Greater => right,
+            };
+        }
+        Option::None
+    }
+    pub fn next_after<Q: ?Sized>(&self, bound: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let mut candidate = None;
+        let mut node = self;
+        while let AVL::Node {
+            key,
+            value,
+            left,
+            right,
+            ..
+        } = node
+        {
+            node = match C::compare(bound, (**key).borrow()) {
+                Ordering::Less => {
+                    candidate = Some((key.as_ref(), value.as_ref()));
+                    left
+                }
+                _ => right,
+            };
+        }
+        candidate
+    }
+    pub fn prev_before<Q: ?Sized>(&self, bound: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let mut candidate = None;
+        let mut node = self;
+        while let AVL::Node {
+            key,
+            value,
+            left,
+            right,
+            ..
+        } = node
+        {
+            node = match C::compare(bound, (**key).borrow()) {
+                Ordering::Greater => {
+                    candidate = Some((key.as_ref(), value.as_ref()));
+                    right
+                }
+                _ => left,
+            };
+        }
+        candidate
+    }
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        self.min()
+    }
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        self.max()
+    }
+    pub fn nth(&self, index: usize) -> Option<(&K, &V)> {
+        match self {
+            AVL::Empty => Option::None,
+            AVL::Node {
+                key,
+                value,
+                left,
+                right,
+                ..
+            } => match index.cmp(&left.len()) {
+                Ordering::Less => left.nth(index),
+                Ordering::Equal => Option::Some((key.as_ref(), value.as_ref())),
+                Ordering::Greater => right.nth(index - left.len() - 1),
+            },
+        }
+    }
+    pub fn rank<Q: ?Sized>(&self, target_key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.count_below(target_key, false)
+    }
+    pub fn count_range<Q: ?Sized, R: RangeBounds<Q>>(&self, range: R) -> usize
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let below_end = match range.end_bound() {
+            Bound::Included(end) => self.count_below(end, true),
+            Bound::Excluded(end) => self.count_below(end, false),
+            Bound::Unbounded => self.len(),
+        };
+        let below_start = match range.start_bound() {
+            Bound::Included(start) => self.count_below(start, false),
+            Bound::Excluded(start) => self.count_below(start, true),
+            Bound::Unbounded => 0,
+        };
+        below_end.saturating_sub(below_start)
+    }
+    // In-order iteration starting at the first key not smaller than the bound
+    pub fn iter_from<Q: ?Sized>(&self, bound: &Q) -> AVLIterator<'_, K, V, C, S>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let mut iter = AVLIterator {
+            front: Vec::new(),
+            back: Vec::new(),
+            remaining: self.len(),
+        };
+        let mut node = self;
+        while let AVL::Node {
+            key, left, right, ..
+        } = node
+        {
+            node = match C::compare(bound, (**key).borrow()) {
+                Ordering::Greater => {
+                    iter.remaining -= left.len() + 1;
+                    right
+                }
+                _ => {
+                    iter.front.push(node);
+                    left
+                }
+            };
+        }
+        iter.push_right_spine(self);
+        iter
+    }
+    // Number of keys smaller than (or, if inclusive, equal to) the bound
+    fn count_below<Q: ?Sized>(&self, bound: &Q, inclusive: bool) -> usize
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let mut count = 0;
+        let mut node = self;
+        while let AVL::Node {
+            key, left, right, ..
+        } = node
+        {
+            node = match C::compare(bound, (**key).borrow()) {
+                Ordering::Less => left,
+                Ordering::Equal if !inclusive => return count + left.len(),
+                Ordering::Equal => return count + left.len() + 1,
+                Ordering::Greater => {
+                    count += left.len() + 1;
+                    right
+                }
+            };
+        }
+        count
+    }
+    fn right_rotation(&self) -> AVL<K, V, C, S> {
+        if let AVL::Node {
+            key: x,
+            value: vx,
+            left: lt,
+            right: t3,
+            ..
+        } = self
+        {
+            if let AVL::Node {
+                key: y,
+                value: vy,
+                left: t1,
+                right: t2,
+                ..
+            } = (*lt).as_ref()
+            {
+                return AVL::node(
+                    y.clone(),
+                    vy.clone(),
+                    t1.clone(),
+                    RefCounter::new(AVL::node(x.clone(), vx.clone(), t2.clone(), t3.clone())),
+                );
+            }
+        }
+        self.clone()
+    }
+    fn right_fix(&self) -> AVL<K, V, C, S> {
+        if let AVL::Node {
+            key: x,
+            value: vx,
+            left: t1,
+            right: t2,
+            ..
+        } = self
+        {
+            if t1.balance_factor() == -1 {
+                return AVL::node(
+                    x.clone(),
+                    vx.clone(),
+                    RefCounter::new(t1.left_rotation()),
+                    t2.clone(),
+                )
+                .right_rotation();
+            } else {
+                return self.right_rotation();
+            }
+        }
+        self.clone()
+    }
+    fn left_rotation(&self) -> AVL<K, V, C, S> {
+        if let AVL::Node {
+            key: x,
+            value: vx,
+            left: t1,
+            right: rt,
+            ..
+        } = self
+        {
+            if let AVL::Node {
+                key: y,
+                value: vy,
+                left: t2,
+                right: t3,
+                ..
+            } = (*rt).as_ref()
+            {
+                return AVL::node(
+                    y.clone(),
+                    vy.clone(),
+                    RefCounter::new(AVL::node(x.clone(), vx.clone(), t1.clone(), t2.clone())),
+                    t3.clone(),
+                );
+            }
+        }
+        self.clone()
+    }
+    fn left_fix(&self) -> AVL<K, V, C, S> {
+        if let AVL::Node {
+            key: x,
+            value: vx,
+            left: t1,
+            right: t2,
+            ..
+        } = self
+        {
+            if t2.balance_factor() == 1 {
+                return AVL::node(
+                    x.clone(),
+                    vx.clone(),
+                    t1.clone(),
+                    RefCounter::new(t2.right_rotation()),
+                )
+                .left_rotation();
+            } else {
+                return self.left_rotation();
+            }
+        }
+        self.clone()
+    }
+    fn fix(&self) -> AVL<K, V, C, S> {
+        match self.balance_factor() {
+            2 => self.right_fix(),
+            -2 => self.left_fix(),
+            _ => self.clone(),
+        }
+    }
+    pub fn put(&self, key: K, value: V) -> AVL<K, V, C, S> {
+        self.put_rc(RefCounter::new(key), RefCounter::new(value))
+    }
+    fn put_rc(&self, key_rc: RefCounter<K>, value_rc: RefCounter<V>) -> AVL<K, V, C, S> {
+        let (path, found) = self.search_path(key_rc.as_ref());
+        let subtree = match found {
+            AVL::Empty => AVL::node(
+                key_rc,
+                value_rc,
+                RefCounter::new(AVL::Empty),
+                RefCounter::new(AVL::Empty),
+            ),
+            AVL::Node { left, right, .. } => {
+                AVL::node(key_rc, value_rc, left.clone(), right.clone())
+            }
+        };
+        AVL::rebuild_path(path, subtree)
+    }
+    // Like put, with a value that may already live in some tree. When the key
+    // holds that very value, the same tree is handed back
+    pub fn put_shared(&self, key: K, value: RefCounter<V>) -> AVL<K, V, C, S> {
+        let unchanged = self
+            .find(&key)
+            .is_some_and(|existing| std::ptr::eq(existing, value.as_ref()));
+        if unchanged {
+            return self.clone();
+        }
+        self.put_rc(RefCounter::new(key), value)
+    }
+    // Like put, but hands back the same tree when the key already holds an
+    // equal value. The flag tells whether anything changed
+    pub fn put_if_changed(&self, key: K, value: V) -> (AVL<K, V, C, S>, bool)
+    where
+        V: PartialEq,
+    {
+        let (path, found) = self.search_path(&key);
+        let subtree = match found {
+            AVL::Empty => AVL::node(
+                RefCounter::new(key),
+                RefCounter::new(value),
+                RefCounter::new(AVL::Empty),
+                RefCounter::new(AVL::Empty),
+            ),
+            AVL::Node {
+                value: existing, ..
+            } if **existing == value => return (self.clone(), false),
+            AVL::Node { left, right, .. } => AVL::node(
+                RefCounter::new(key),
+                RefCounter::new(value),
+                left.clone(),
+                right.clone(),
+            ),
+        };
+        (AVL::rebuild_path(path, subtree), true)
+    }
+    // Same as put, but nodes that are not shared with any other tree are
+    // updated in place instead of being copied
+    pub fn put_owned(mut self, key: K, value: V) -> AVL<K, V, C, S> {
+        self.put_in_place(RefCounter::new(key), RefCounter::new(value));
+        self
+    }
+    fn put_in_place(&mut self, key_rc: RefCounter<K>, value_rc: RefCounter<V>) {
+        match self {
+            AVL::Empty => {
+                *self = AVL::node(
+                    key_rc,
+                    value_rc,
+                    RefCounter::new(AVL::Empty),
+                    RefCounter::new(AVL::Empty),
+                );
+                return;
+            }
+            AVL::Node {
+                key,
+                value,
+                left,
+                right,
+                ..
+            } => match C::compare(key_rc.as_ref(), key.as_ref()) {
+                Ordering::Less => AVL::put_in_child(left, key_rc, value_rc),
+                Ordering::Equal => {
+                    // Falls through so the summary sees the new entry
+                    *key = key_rc;
+                    *value = value_rc;
+                }
+                Ordering::Greater => AVL::put_in_child(right, key_rc, value_rc),
+            },
+        }
+        self.refresh();
+        if self.balance_factor().abs() > 1 {
+            *self = self.fix();
+        }
+    }
+    fn put_in_child(
+        child: &mut RefCounter<AVL<K, V, C, S>>,
+        key_rc: RefCounter<K>,
+        value_rc: RefCounter<V>,
+    ) {
+        match RefCounter::get_mut(child) {
+            Some(unique) => unique.put_in_place(key_rc, value_rc),
+            None => *child = RefCounter::new(child.put_rc(key_rc, value_rc)),
+        }
+    }
+    // Nodes created by earlier entries are not shared yet, so the later
+    // entries update them in place instead of copying the path again
+    pub fn extend<I: IntoIterator<Item = (K, V)>>(&self, entries: I) -> AVL<K, V, C, S> {
+        let mut tree = self.clone();
+        for (key, value) in entries {
+            tree.put_in_place(RefCounter::new(key), RefCounter::new(value));
+        }
+        tree
+    }
+    pub fn put_with<F: FnOnce(&V, V) -> V>(&self, key: K, value: V, merge: F) -> AVL<K, V, C, S> {
+        let (path, found) = self.search_path(&key);
+        let subtree = match found {
+            AVL::Empty => AVL::node(
+                RefCounter::new(key),
+                RefCounter::new(value),
+                RefCounter::new(AVL::Empty),
+                RefCounter::new(AVL::Empty),
+            ),
+            AVL::Node {
+                key: existing_key,
+                value: existing_value,
+                left,
+                right,
+                ..
+            } => AVL::node(
+                existing_key.clone(),
+                RefCounter::new(merge(existing_value, value)),
+                left.clone(),
+                right.clone(),
+            ),
+        };
+        AVL::rebuild_path(path, subtree)
+    }
+    // Walks down to the node holding target_key (or the empty leaf where it
+    // would go), recording every ancestor and the side that was taken
+    fn search_path<Q: ?Sized>(&self, target_key: &Q) -> Located<'_, K, V, C, S>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let mut path = Vec::new();
+        let mut node = self;
+        while let AVL::Node {
+            key, left, right, ..
+        } = node
+        {
+            let side = C::compare(target_key, (**key).borrow());
+            let next = match side {
+                Ordering::Less => left,
+                Ordering::Equal => break,
+                Ordering::Greater => right,
+            };
+            path.push((node, side));
+            node = next;
+        }
+        (path, node)
+    }
+    fn rebuild_path(
+        mut path: Path<'_, K, V, C, S>,
+        mut subtree: AVL<K, V, C, S>,
+    ) -> AVL<K, V, C, S> {
+        while let Some((parent, side)) = path.pop() {
+            if let AVL::Node {
+                key,
+                value,
+                left,
+                right,
+                ..
+            } = parent
+            {
+                subtree = match side {
+                    Ordering::Less => AVL::node(
+                        key.clone(),
+                        value.clone(),
+                        RefCounter::new(subtree),
+                        right.clone(),
+                    ),
+                    _ => AVL::node(
+                        key.clone(),
+                        value.clone(),
+                        left.clone(),
+                        RefCounter::new(subtree),
+                    ),
+                }
+                .fix();
+            }
+        }
+        subtree
+    }
+    pub fn delete<Q: ?Sized>(&self, target_key: &Q) -> AVL<K, V, C, S>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        match self.remove(target_key) {
+            Some((_, deleted)) => deleted,
+            None => self.clone(),
+        }
+    }
+    pub fn remove<Q: ?Sized>(&self, target_key: &Q) -> Option<Removed<K, V, C, S>>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let (path, found) = self.search_path(target_key);
+        match found {
+            AVL::Empty => None,
+            AVL::Node { value, .. } => {
+                Some((value.clone(), AVL::rebuild_path(path, found.delete_root())))
+            }
+        }
+    }
+
+    fn delete_root(&self) -> AVL<K, V, C, S> {
+        match self {
+            AVL::Empty => AVL::Empty,
+            AVL::Node { left, right, .. } => {
+                // Node with only one child or no child
+                if left.is_empty() {
+                    return right.as_ref().clone();
+                } else if right.is_empty() {
+                    return left.as_ref().clone();
+                }
+
+                // Node with two children, get the inorder predecessor (maximum value in the left subtree)
+                let inorder_predecessor = left.find_max();
+                if let Some((pred_key, pred_value)) = inorder_predecessor {
+                    AVL::node(
+                        pred_key,
+                        pred_value,
+                        RefCounter::new(left.delete_max()),
+                        right.clone(),
+                    )
+                    .fix()
+                } else {
+                    self.clone()
+                }
+            }
+        }
+    }
+    pub fn update<F>(&self, target_key: &K, f: F) -> AVL<K, V, C, S>
+    where
+        K: Clone,
+        F: FnOnce(Option<&V>) -> Option<V>,
+    {
+        self.update_node(target_key, f)
+            .unwrap_or_else(|| self.clone())
+    }
+    // Returns None when the tree is left untouched, so no path gets copied
+    fn update_node<F>(&self, target_key: &K, f: F) -> Option<AVL<K, V, C, S>>
+    where
+        K: Clone,
+        F: FnOnce(Option<&V>) -> Option<V>,
+    {
+        let (path, found) = self.search_path(target_key);
+        let subtree = match found {
+            AVL::Empty => AVL::node(
+                RefCounter::new(target_key.clone()),
+                RefCounter::new(f(None)?),
+                RefCounter::new(AVL::Empty),
+                RefCounter::new(AVL::Empty),
+            ),
+            AVL::Node {
+                key,
+                value,
+                left,
+                right,
+                ..
+            } => match f(Some(value)) {
+                Some(new_value) => AVL::node(
+                    key.clone(),
+                    RefCounter::new(new_value),
+                    left.clone(),
+                    right.clone(),
+                ),
+                None => found.delete_root(),
+            },
+        };
+        Some(AVL::rebuild_path(path, subtree))
+    }
+
+    pub fn min(&self) -> Option<(&K, &V)> {
+        match self {
+            AVL::Empty => None,
+            AVL::Node {
+                key, value, left, ..
+            } => left.min().or(Some((key.as_ref(), value.as_ref()))),
+        }
+    }
+    pub fn max(&self) -> Option<(&K, &V)> {
+        match self {
+            AVL::Empty => None,
+            AVL::Node {
+                key, value, right, ..
+            } => right.max().or(Some((key.as_ref(), value.as_ref()))),
+        }
+    }
+    pub fn pop_min(&self) -> Option<Popped<'_, K, V, C, S>> {
+        let (key, value) = self.min()?;
+        Some((key, value, self.delete_min()))
+    }
+    pub fn pop_max(&self) -> Option<Popped<'_, K, V, C, S>> {
+        let (key, value) = self.max()?;
+        Some((key, value, self.delete_max()))
+    }
+    fn delete_min(&self) -> AVL<K, V, C, S> {
+        match self {
+            AVL::Empty => AVL::Empty,
+            AVL::Node {
+                key,
+                value,
+                left,
+                right,
+                ..
+            } => {
+                if left.is_empty() {
+                    right.as_ref().clone()
+                } else {
+                    AVL::node(
+                        key.clone(),
+                        value.clone(),
+                        RefCounter::new(left.delete_min()),
+                        right.clone(),
+                    )
+                    .fix()
+                }
+            }
+        }
+    }
+    fn delete_max(&self) -> AVL<K, V, C, S> {
+        match self {
+            AVL::Empty => AVL::Empty,
+            AVL::Node {
+                key,
+                value,
+                left,
+                right,
+                ..
+            } => {
+                if right.is_empty() {
+                    left.as_ref().clone()
+                } else {
+                    AVL::node(
+                        key.clone(),
+                        value.clone(),
+                        left.clone(),
+                        RefCounter::new(right.delete_max()),
+                    )
+                    .fix()
+                }
+            }
+        }
+    }
+
+    fn find_max(&self) -> Option<(RefCounter<K>, RefCounter<V>)> {
+        match self {
+            AVL::Empty => None,
+            AVL::Node {
+                key, value, right, ..
+            } => {
+                if right.is_empty() {
+                    Some((key.clone(), value.clone()))
+                } else {
+                    right.find_max()
+                }
+            }
+        }
+    }
+    fn join(
+        left: &AVL<K, V, C, S>,
+        key: RefCounter<K>,
+        value: RefCounter<V>,
+        right: &AVL<K, V, C, S>,
+    ) -> AVL<K, V, C, S> {
+        if left.height() > right.height() + 1 {
+            if let AVL::Node {
+                key: lk,
+                value: lv,
+                left: ll,
+                right: lr,
+                ..
+            } = left
+            {
+                return AVL::node(
+                    lk.clone(),
+                    lv.clone(),
+                    ll.clone(),
+                    RefCounter::new(AVL::join(lr, key, value, right)),
+                )
+                .fix();
+            }
+        } else if right.height() > left.height() + 1 {
+            if let AVL::Node {
+                key: rk,
+                value: rv,
+                left: rl,
+                right: rr,
+                ..
+            } = right
+            {
+                return AVL::node(
+                    rk.clone(),
+                    rv.clone(),
+                    RefCounter::new(AVL::join(left, key, value, rl)),
+                    rr.clone(),
+                )
+                .fix();
+            }
+        }
+        AVL::node(
+            key,
+            value,
+            RefCounter::new(left.clone()),
+            RefCounter::new(right.clone()),
+        )
+    }
+    fn join2(left: &AVL<K, V, C, S>, right: &AVL<K, V, C, S>) -> AVL<K, V, C, S> {
+        match left.find_max() {
+            None => right.clone(),
+            Some((key, value)) => AVL::join(&left.delete_max(), key, value, right),
+        }
+    }
+    fn split(&self, target_key: &K) -> Split<K, V, C, S> {
+        match self {
+            AVL::Empty => (AVL::Empty, None, AVL::Empty),
+            AVL::Node {
+                key,
+                value,
+                left,
+                right,
+                ..
+            } => match C::compare(target_key, key) {
+                Ordering::Less => {
+                    let (less, found, greater) = left.split(target_key);
+                    let greater = AVL::join(&greater, key.clone(), value.clone(), right);
+                    (less, found, greater)
+                }
+                Ordering::Equal => (
+                    left.as_ref().clone(),
+                    Some(value.clone()),
+                    right.as_ref().clone(),
+                ),
+                Ordering::Greater => {
+                    let (less, found, greater) = right.split(target_key);
+                    let less = AVL::join(left, key.clone(), value.clone(), &less);
+                    (less, found, greater)
+                }
+            },
+        }
+    }
+
+    // Splits into the keys below the bound (including it, if inclusive) and the rest
+    fn split_at_bound<Q: ?Sized>(&self, bound: &Q, inclusive: bool) -> Halves<K, V, C, S>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        match self {
+            AVL::Empty => (AVL::Empty, AVL::Empty),
+            AVL::Node {
+                key,
+                value,
+                left,
+                right,
+                ..
+            } => {
+                let goes_left = match C::compare((**key).borrow(), bound) {
+                    Ordering::Less => true,
+                    Ordering::Equal => inclusive,
+                    Ordering::Greater => false,
+                };
+                if goes_left {
+                    let (below, above) = right.split_at_bound(bound, inclusive);
+                    (AVL::join(left, key.clone(), value.clone(), &below), above)
+                } else {
+                    let (below, above) = left.split_at_bound(bound, inclusive);
+                    (below, AVL::join(&above, key.clone(), value.clone(), right))
+                }
+            }
+        }
+    }
+    // Index of the first key for which the predicate is false, assuming it
+    // holds for a prefix of the keys and fails for the rest
+    pub fn partition_point<P: FnMut(&K) -> bool>(&self, mut pred: P) -> usize {
+        let mut count = 0;
+        let mut node = self;
+        while let AVL::Node {
+            key, left, right, ..
+        } = node
+        {
+            node = if pred(key) {
+                count += left.len() + 1;
+                right
+            } else {
+                left
+            };
+        }
+        count
+    }
+    // Splits at the partition point: the keys the predicate holds for and the rest
+    pub fn split_at_partition<P: FnMut(&K) -> bool>(&self, mut pred: P) -> Halves<K, V, C, S> {
+        self.split_by(&mut pred)
+    }
+    fn split_by<P: FnMut(&K) -> bool>(&self, pred: &mut P) -> Halves<K, V, C, S> {
+        match self {
+            AVL::Empty => (AVL::Empty, AVL::Empty),
+            AVL::Node {
+                key,
+                value,
+                left,
+                right,
+                ..
+            } => {
+                if pred(key) {
+                    let (below, above) = right.split_by(pred);
+                    (AVL::join(left, key.clone(), value.clone(), &below), above)
+                } else {
+                    let (below, above) = left.split_by(pred);
+                    (below, AVL::join(&above, key.clone(), value.clone(), right))
                 }
-                .right_rotation();
-            } else {
-                return self.right_rotation();
             }
         }
-        return self.clone();
     }
-    fn left_rotation(&self) -> AVL<K, V> {
-        if let AVL::Node {
-            key: x,
-            value: vx,
-            left: t1,
-            right: rt,
-        } = self
+    pub fn delete_range<Q: ?Sized, R: RangeBounds<Q>>(&self, range: R) -> AVL<K, V, C, S>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        if self.count_range((range.start_bound(), range.end_bound())) == 0 {
+            return self.clone();
+        }
+        let before = match range.start_bound() {
+            Bound::Included(start) => self.split_at_bound(start, false).0,
+            Bound::Excluded(start) => self.split_at_bound(start, true).0,
+            Bound::Unbounded => AVL::Empty,
+        };
+        let after = match range.end_bound() {
+            Bound::Included(end) => self.split_at_bound(end, true).1,
+            Bound::Excluded(end) => self.split_at_bound(end, false).1,
+            Bound::Unbounded => AVL::Empty,
+        };
+        AVL::join2(&before, &after)
+    }
+
+    pub fn cursor_front(&self) -> Cursor<'_, K, V, C, S> {
+        let mut cursor = Cursor {
+            root: self,
+            path: Vec::new(),
+            current: None,
+        };
+        cursor.descend(self, Ordering::Less);
+        cursor
+    }
+    pub fn cursor_back(&self) -> Cursor<'_, K, V, C, S> {
+        let mut cursor = Cursor {
+            root: self,
+            path: Vec::new(),
+            current: None,
+        };
+        cursor.descend(self, Ordering::Greater);
+        cursor
+    }
+    // Places the cursor at the first key not less than the bound
+    pub fn cursor_at<Q: ?Sized>(&self, bound: &Q) -> Cursor<'_, K, V, C, S>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let mut path = Vec::new();
+        let mut found = None;
+        let mut node = self;
+        while let AVL::Node {
+            key, left, right, ..
+        } = node
         {
-            if let AVL::Node {
-                key: y,
-                value: vy,
-                left: t2,
-                right: t3,
-            } = (*rt).as_ref()
-            {
-                return AVL::Node {
-                    key: y.clone(),
-                    value: vy.clone(),
-                    left: RefCounter::new(AVL::Node {
-                        key: x.clone(),
-                        value: vx.clone(),
-                        left: t1.clone(),
-                        right: t2.clone(),
-                    }),
-                    right: t3.clone(),
-                };
+            match C::compare(bound, (**key).borrow()) {
+                Ordering::Greater => {
+                    path.push((node, Ordering::Greater));
+                    node = right;
+                }
+                _ => {
+                    found = Some((node, path.len()));
+                    path.push((node, Ordering::Less));
+                    node = left;
+                }
             }
         }
-        return self.clone();
+        let current = found.map(|(found_node, depth)| {
+            path.truncate(depth);
+            found_node
+        });
+        if current.is_none() {
+            path.clear();
+        }
+        Cursor {
+            root: self,
+            path,
+            current,
+        }
+    }
+    pub fn validate(&self) -> Result<(), InvariantViolation> {
+        self.validate_node(0, None, None)
     }
-    fn left_fix(&self) -> AVL<K, V> {
+    fn validate_node(
+        &self,
+        offset: usize,
+        lower: Option<&K>,
+        upper: Option<&K>,
+    ) -> Result<(), InvariantViolation> {
         if let AVL::Node {
-            key: x,
-            value: vx,
-            left: t1,
-            right: t2,
+            key,
+            left,
+            right,
+            size,
+            height,
+            ..
         } = self
         {
-            if t2.diff() == 1 {
-                return AVL::Node {
-                    key: x.clone(),
-                    value: vx.clone(),
-                    left: t1.clone(),
-                    right: RefCounter::new(t2.right_rotation()),
-                }
-                .left_rotation();
-            } else {
-                return self.left_rotation();
+            let index = offset + left.len();
+            let above_lower = lower.is_none_or(|lower| C::compare(lower, key) == Ordering::Less);
+            let below_upper = upper.is_none_or(|upper| C::compare(key, upper) == Ordering::Less);
+            if !above_lower || !below_upper {
+                return Err(InvariantViolation::Unordered { index });
             }
+            left.validate_node(offset, lower, Some(key))?;
+            if *size != 1 + left.len() + right.len() {
+                return Err(InvariantViolation::WrongSize { index });
+            }
+            if *height != 1 + max(left.height(), right.height()) {
+                return Err(InvariantViolation::WrongHeight { index });
+            }
+            if self.balance_factor().abs() > 1 {
+                return Err(InvariantViolation::Unbalanced { index });
+            }
+            right.validate_node(index + 1, Some(key), upper)?;
         }
-        return self.clone();
+        Ok(())
     }
-    fn fix(&self) -> AVL<K, V> {
-        match self.diff() {
-            2 => self.right_fix(),
-            -2 => self.left_fix(),
-            _ => self.clone(),
+    pub fn diff<'a>(&'a self, other: &'a AVL<K, V, C, S>) -> DiffIterator<'a, K, V, C, S> {
+        let mut diff = DiffIterator {
+            old: Vec::new(),
+            new: Vec::new(),
+        };
+        DiffIterator::push_tree(&mut diff.old, self);
+        DiffIterator::push_tree(&mut diff.new, other);
+        diff
+    }
+    pub fn merge_iter<'a, F: FnMut(&V, &V) -> Ordering>(
+        &'a self,
+        other: &'a AVL<K, V, C, S>,
+        tie_break: F,
+    ) -> MergeIterator<'a, K, V, F, C, S> {
+        MergeIterator {
+            left: self.iter().peekable(),
+            right: other.iter().peekable(),
+            tie_break,
         }
     }
-    pub fn put(&self, key: K, value: V) -> AVL<K, V> {
-        self.put_rc(RefCounter::new(key), RefCounter::new(value))
+    pub fn filter<F: FnMut(&K, &V) -> bool>(&self, mut predicate: F) -> AVL<K, V, C, S> {
+        self.filter_node(&mut predicate)
+            .unwrap_or_else(|| self.clone())
     }
-    fn put_rc(&self, key_rc: RefCounter<K>, value_rc: RefCounter<V>) -> AVL<K, V> {
+    // Returns None when every entry is kept, so untouched subtrees stay shared
+    fn filter_node<F: FnMut(&K, &V) -> bool>(&self, predicate: &mut F) -> Option<AVL<K, V, C, S>> {
         match self {
-            AVL::Empty => AVL::Node {
-                key: key_rc,
-                value: value_rc,
-                left: RefCounter::new(AVL::Empty),
-                right: RefCounter::new(AVL::Empty),
-            },
+            AVL::Empty => None,
             AVL::Node {
                 key,
                 value,
                 left,
                 right,
-            } => match key_rc.cmp(key) {
-                std::cmp::Ordering::Less => AVL::Node {
-                    key: key.clone(),
-                    value: value.clone(),
-                    left: RefCounter::new(left.put_rc(key_rc, value_rc)),
-                    right: right.clone(),
-                }
-                .fix(),
-                std::cmp::Ordering::Equal => AVL::Node {
-                    key: key_rc,
-                    value: value_rc,
-                    left: left.clone(),
-                    right: right.clone(),
-                },
-                std::cmp::Ordering::Greater => AVL::Node {
-                    key: key.clone(),
-                    value: value.clone(),
-                    left: left.clone(),
-                    right: RefCounter::new(right.put_rc(key_rc, value_rc)),
+                ..
+            } => {
+                let left_filtered = left.filter_node(predicate);
+                let keep = predicate(key, value);
+                let right_filtered = right.filter_node(predicate);
+                if keep && left_filtered.is_none() && right_filtered.is_none() {
+                    return None;
                 }
-                .fix(),
-            },
+                let left_filtered = left_filtered.unwrap_or_else(|| left.as_ref().clone());
+                let right_filtered = right_filtered.unwrap_or_else(|| right.as_ref().clone());
+                if keep {
+                    Some(AVL::join(
+                        &left_filtered,
+                        key.clone(),
+                        value.clone(),
+                        &right_filtered,
+                    ))
+                } else {
+                    Some(AVL::join2(&left_filtered, &right_filtered))
+                }
+            }
         }
     }
-    pub fn delete(&self, target_key: &K) -> AVL<K, V> {
-        match self {
-            AVL::Empty => AVL::Empty,
+
+    pub fn union<F: Fn(&V, &V) -> V>(&self, other: &AVL<K, V, C, S>, merge: F) -> AVL<K, V, C, S> {
+        self.union_with(other, &merge)
+    }
+    fn union_with<F: Fn(&V, &V) -> V>(
+        &self,
+        other: &AVL<K, V, C, S>,
+        merge: &F,
+    ) -> AVL<K, V, C, S> {
+        match other {
+            AVL::Empty => self.clone(),
+            _ if self.is_empty() => other.clone(),
             AVL::Node {
                 key,
                 value,
                 left,
                 right,
+                ..
             } => {
-                match target_key.cmp(key) {
-                    std::cmp::Ordering::Less => {
-                        let left_deleted = left.delete(target_key);
-                        AVL::Node {
-                            key: key.clone(),
-                            value: value.clone(),
-                            left: RefCounter::new(left_deleted),
-                            right: right.clone(),
-                        }
-                        .fix()
-                    }
-                    std::cmp::Ordering::Equal => {
-                        // Node with only one child or no child
-                        if left.is_empty() {
-                            return right.as_ref().clone();
-                        } else if right.is_empty() {
-                            return left.as_ref().clone();
-                        }
-
-                        // Node with two children, get the inorder predecessor (maximum value in the left subtree)
-                        let inorder_predecessor = left.find_max();
-                        if let Some((pred_key, pred_value)) = inorder_predecessor {
-                            let left_deleted = left.delete(&pred_key);
-                            AVL::Node {
-                                key: pred_key.clone(),
-                                value: pred_value.clone(),
-                                left: RefCounter::new(left_deleted),
-                                right: right.clone(),
-                            }
-                            .fix()
-                        } else {
-                            self.clone()
-                        }
-                    }
-                    std::cmp::Ordering::Greater => {
-                        let right_deleted = right.delete(target_key);
-                        AVL::Node {
-                            key: key.clone(),
-                            value: value.clone(),
-                            left: left.clone(),
-                            right: RefCounter::new(right_deleted),
-                        }
-                        .fix()
-                    }
-                }
+                let (less, found, greater) = self.split(key);
+                let value = match found {
+                    Some(own_value) => RefCounter::new(merge(&own_value, value)),
+                    None => value.clone(),
+                };
+                AVL::join(
+                    &less.union_with(left, merge),
+                    key.clone(),
+                    value,
+                    &greater.union_with(right, merge),
+                )
             }
         }
     }
-
-    fn find_max(&self) -> Option<(RefCounter<K>, RefCounter<V>)> {
-        match self {
-            AVL::Empty => None,
+    pub fn intersection<F: Fn(&V, &V) -> V>(
+        &self,
+        other: &AVL<K, V, C, S>,
+        merge: F,
+    ) -> AVL<K, V, C, S> {
+        self.intersection_with(other, &merge)
+    }
+    fn intersection_with<F: Fn(&V, &V) -> V>(
+        &self,
+        other: &AVL<K, V, C, S>,
+        merge: &F,
+    ) -> AVL<K, V, C, S> {
+        match other {
+            AVL::Empty => AVL::Empty,
+            _ if self.is_empty() => AVL::Empty,
             AVL::Node {
                 key,
                 value,
-                left: _,
+                left,
                 right,
+                ..
             } => {
-                if right.is_empty() {
-                    Some((key.clone(), value.clone()))
-                } else {
-                    right.find_max()
+                let (less, found, greater) = self.split(key);
+                let less = less.intersection_with(left, merge);
+                let greater = greater.intersection_with(right, merge);
+                match found {
+                    Some(own_value) => AVL::join(
+                        &less,
+                        key.clone(),
+                        RefCounter::new(merge(&own_value, value)),
+                        &greater,
+                    ),
+                    None => AVL::join2(&less, &greater),
                 }
             }
         }
     }
+    pub fn difference(&self, other: &AVL<K, V, C, S>) -> AVL<K, V, C, S> {
+        match other {
+            AVL::Empty => self.clone(),
+            _ if self.is_empty() => AVL::Empty,
+            AVL::Node {
+                key, left, right, ..
+            } => {
+                let (less, _, greater) = self.split(key);
+                AVL::join2(&less.difference(left), &greater.difference(right))
+            }
+        }
+    }
+}
+
+impl<K, V, C: Compare<K>, S: Summary<K, V>> FromIterator<(K, V)> for AVL<K, V, C, S> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut entries: Vec<(K, V)> = iter.into_iter().collect();
+        // Stable sort, so the last value given for a key wins
+        entries.sort_by(|a, b| C::compare(&a.0, &b.0));
+        AVL::from_sorted_entries(entries)
+    }
+}
+
+impl<K, C: Compare<K>, S: Summary<K, ()>> FromIterator<K> for AVL<K, (), C, S> {
+    fn from_iter<I: IntoIterator<Item = K>>(iter: I) -> Self {
+        iter.into_iter().map(|key| (key, ())).collect()
+    }
+}
+
+impl<K: Ord, V> From<BTreeMap<K, V>> for AVL<K, V> {
+    fn from(map: BTreeMap<K, V>) -> Self {
+        AVL::from_sorted_iter(map)
+    }
+}
+
+// Entries still shared with other versions are cloned, unique ones are moved
+impl<K: Ord + Clone, V: Clone> From<AVL<K, V>> for BTreeMap<K, V> {
+    fn from(tree: AVL<K, V>) -> Self {
+        tree.into_iter().collect()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn is_balanced<K, V, C>(t: &AVL<K, V, C>) -> bool {
+        match t {
+            AVL::Empty => true,
+            AVL::Node {
+                left,
+                right,
+                size,
+                height,
+                ..
+            } => {
+                (left.height() - right.height()).abs() <= 1
+                    && *size == 1 + left.len() + right.len()
+                    && *height == 1 + max(left.height(), right.height())
+                    && is_balanced(left)
+                    && is_balanced(right)
+            }
+        }
+    }
+
     #[test]
     fn test_avl_set() {
         let l = AVL::empty().insert(1).insert(2).insert(3).insert(4);
@@ -359,4 +2074,791 @@ mod tests {
         assert!(l.search(&4));
         assert!(l.search(&5));
     }
+    #[test]
+    fn test_avl_len() {
+        let l = AVL::empty().insert(1).insert(2).insert(3).insert(4);
+        assert_eq!(l.len(), 4);
+        assert_eq!(l.insert(2).len(), 4);
+        let l2 = l.delete(&3);
+        assert_eq!(l2.len(), 3);
+        assert_eq!(l.len(), 4);
+        assert_eq!(l2.delete(&42).len(), 3);
+        assert_eq!(AVL::<i32>::empty().len(), 0);
+    }
+    #[test]
+    fn test_avl_order_statistics() {
+        let l = AVL::empty()
+            .put(30, "c")
+            .put(10, "a")
+            .put(20, "b")
+            .put(40, "d");
+        assert_eq!(l.nth(0), Some((&10, &"a")));
+        assert_eq!(l.nth(2), Some((&30, &"c")));
+        assert_eq!(l.nth(3), Some((&40, &"d")));
+        assert!(l.nth(4).is_none());
+        assert_eq!(l.rank(&10), 0);
+        assert_eq!(l.rank(&30), 2);
+        assert_eq!(l.rank(&25), 2);
+        assert_eq!(l.rank(&99), 4);
+        for i in 0..l.len() {
+            let (k, _) = l.nth(i).unwrap();
+            assert_eq!(l.rank(k), i);
+        }
+    }
+    #[test]
+    fn test_avl_min_max() {
+        let empty: AVL<i32, &str> = AVL::empty();
+        assert!(empty.min().is_none());
+        assert!(empty.pop_max().is_none());
+
+        let l = AVL::empty().put(2, "b").put(1, "a").put(3, "c").put(4, "d");
+        assert_eq!(l.min(), Some((&1, &"a")));
+        assert_eq!(l.max(), Some((&4, &"d")));
+
+        let (k, v, rest) = l.pop_min().unwrap();
+        assert_eq!((k, v), (&1, &"a"));
+        assert_eq!(rest.len(), 3);
+        assert_eq!(rest.min(), Some((&2, &"b")));
+
+        let (k, v, rest) = rest.pop_max().unwrap();
+        assert_eq!((k, v), (&4, &"d"));
+        assert_eq!(rest.len(), 2);
+        assert_eq!(rest.max(), Some((&3, &"c")));
+        assert_eq!(l.len(), 4);
+    }
+    #[test]
+    fn test_avl_set_operations() {
+        let a = (0..20).fold(AVL::empty(), |t, i| t.put(i, i));
+        let b = (10..40).fold(AVL::empty(), |t, i| t.put(i, 100 * i));
+
+        let u = a.union(&b, |x, y| x + y);
+        assert_eq!(u.len(), 40);
+        assert_eq!(u.find(&5), Some(&5));
+        assert_eq!(u.find(&15), Some(&(15 + 1500)));
+        assert_eq!(u.find(&30), Some(&3000));
+
+        let i = a.intersection(&b, |x, _| *x);
+        assert_eq!(i.len(), 10);
+        assert_eq!(i.min(), Some((&10, &10)));
+        assert_eq!(i.max(), Some((&19, &19)));
+
+        let d = a.difference(&b);
+        assert_eq!(d.len(), 10);
+        assert!(d.find(&9).is_some());
+        assert!(d.find(&10).is_none());
+
+        assert_eq!(a.len(), 20);
+        assert_eq!(b.len(), 30);
+        for t in [&u, &i, &d] {
+            for idx in 0..t.len() {
+                let (k, _) = t.nth(idx).unwrap();
+                assert_eq!(t.rank(k), idx);
+            }
+            assert!(is_balanced(t));
+        }
+    }
+    #[test]
+    fn test_avl_from_sorted_iter() {
+        let l = AVL::from_sorted_iter((0..1000).map(|i| (i, i * 2)));
+        assert_eq!(l.len(), 1000);
+        assert!(is_balanced(&l));
+        assert_eq!(l.height(), 10);
+        for i in 0..1000 {
+            assert_eq!(l.find(&i), Some(&(i * 2)));
+        }
+
+        let l = AVL::from_sorted_iter(vec![(1, "a"), (2, "b"), (2, "c"), (3, "d")]);
+        assert_eq!(l.len(), 3);
+        assert_eq!(l.find(&2), Some(&"c"));
+        assert!(AVL::<i32, i32>::from_sorted_iter(Vec::new()).is_empty());
+    }
+    #[test]
+    #[should_panic(expected = "from_sorted_iter requires ascending keys")]
+    fn test_avl_from_sorted_iter_unsorted() {
+        AVL::from_sorted_iter(vec![(3, 'c'), (1, 'a'), (2, 'b')]);
+    }
+    #[test]
+    fn test_avl_from_iter() {
+        let m: OrderedMap<_, _> = vec![(3, "c"), (1, "a"), (2, "b"), (1, "z")]
+            .into_iter()
+            .collect();
+        assert_eq!(m.len(), 3);
+        assert_eq!(m.find(&1), Some(&"z"));
+        assert_eq!(m.min(), Some((&1, &"z")));
+        assert!(is_balanced(&m));
+
+        let s: AVL<_> = (0..100).rev().collect();
+        assert_eq!(s.len(), 100);
+        assert!(s.search(&42));
+        assert!(!s.search(&100));
+        assert!(is_balanced(&s));
+    }
+    #[test]
+    fn test_avl_update() {
+        let counts = ["a", "b", "a", "c", "a"]
+            .iter()
+            .fold(AVL::empty(), |m, word| {
+                m.update(word, |count| Some(count.unwrap_or(&0) + 1))
+            });
+        assert_eq!(counts.find(&"a"), Some(&3));
+        assert_eq!(counts.find(&"b"), Some(&1));
+        assert_eq!(counts.len(), 3);
+
+        let removed = counts.update(&"a", |_| None);
+        assert_eq!(removed.find(&"a"), None);
+        assert_eq!(removed.len(), 2);
+        assert_eq!(counts.find(&"a"), Some(&3));
+
+        let untouched = counts.update(&"z", |_| None);
+        assert_eq!(untouched.len(), 3);
+        assert!(untouched.find(&"z").is_none());
+    }
+    #[test]
+    fn test_avl_remove() {
+        let l = AVL::empty().put(1, "a").put(2, "b").put(3, "c");
+        let (removed, l2) = l.remove(&2).unwrap();
+        assert_eq!(*removed, "b");
+        assert_eq!(l2.len(), 2);
+        assert!(l2.find(&2).is_none());
+        assert_eq!(l.find(&2), Some(&"b"));
+        assert!(l2.remove(&2).is_none());
+        assert!(AVL::<i32, i32>::empty().remove(&1).is_none());
+    }
+    #[test]
+    fn test_avl_iterators() {
+        let m: OrderedMap<_, _> = vec![(3, "c"), (1, "a"), (2, "b")].into_iter().collect();
+        let entries: Vec<_> = m.iter().collect();
+        assert_eq!(entries, vec![(&1, &"a"), (&2, &"b"), (&3, &"c")]);
+        assert_eq!(m.keys().collect::<Vec<_>>(), vec![&1, &2, &3]);
+        assert_eq!(m.values().collect::<Vec<_>>(), vec![&"a", &"b", &"c"]);
+        assert!(AVL::<i32, i32>::empty().iter().next().is_none());
+
+        let s: AVL<_> = (0..100).rev().collect();
+        assert!(s.keys().copied().eq(0..100));
+    }
+    #[test]
+    fn test_avl_map_values() {
+        let m = AVL::empty().put(1, "1").put(2, "22").put(3, "333");
+        let lengths = m.map_values(|v| v.len());
+        assert_eq!(lengths.find(&2), Some(&2));
+        assert_eq!(lengths.len(), 3);
+        assert!(lengths.values().copied().eq(1..=3));
+        assert_eq!(m.find(&3), Some(&"333"));
+    }
+    #[test]
+    fn test_avl_filter() {
+        let m: OrderedMap<_, _> = (0..100).map(|i| (i, i * 10)).collect();
+        let even = m.filter(|k, _| k % 2 == 0);
+        assert_eq!(even.len(), 50);
+        assert!(even.keys().all(|k| k % 2 == 0));
+        assert!(is_balanced(&even));
+
+        let big = m.filter(|_, v| *v >= 900);
+        assert!(big.keys().copied().eq(90..100));
+        assert!(is_balanced(&big));
+
+        assert_eq!(m.filter(|_, _| true).len(), 100);
+        assert!(m.filter(|_, _| false).is_empty());
+        assert_eq!(m.len(), 100);
+    }
+    #[test]
+    fn test_avl_debug() {
+        let m = AVL::empty().put(2, "b").put(1, "a");
+        assert_eq!(format!("{:?}", m), r#"{1: "a", 2: "b"}"#);
+        assert_eq!(format!("{:?}", AVL::<i32, i32>::empty()), "{}");
+    }
+    #[test]
+    fn test_avl_eq() {
+        let a = AVL::empty().put(1, "a").put(2, "b").put(3, "c");
+        let b = AVL::empty().put(3, "c").put(2, "b").put(1, "a");
+        assert_eq!(a, b);
+        assert_eq!(a, a.clone());
+        assert_ne!(a, a.put(2, "z"));
+        assert_ne!(a, a.delete(&2));
+        assert_eq!(a.delete(&2), b.delete(&2));
+        assert_eq!(AVL::<i32, i32>::empty(), AVL::empty());
+        assert_ne!(a.put(4, "d").delete(&1), a.put(0, "z").delete(&1));
+    }
+    #[test]
+    fn test_avl_eq_skips_shared_subtrees() {
+        use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+        static COMPARED: AtomicUsize = AtomicUsize::new(0);
+        struct Value(i32);
+        impl PartialEq for Value {
+            fn eq(&self, other: &Self) -> bool {
+                COMPARED.fetch_add(1, Relaxed);
+                self.0 == other.0
+            }
+        }
+
+        let a = (0..10_000).fold(AVL::empty(), |t, i| t.put(i, Value(i)));
+        let b = a.put(5000, Value(5000));
+        let c = a.put(5000, Value(-1));
+        COMPARED.store(0, Relaxed);
+        assert!(a == b);
+        assert!(a != c);
+        assert!(COMPARED.load(Relaxed) <= 2);
+        assert!(a.clone() == a);
+    }
+    #[test]
+    fn test_avl_ordered_set_path() {
+        let s: crate::avl::OrderedSet<i32> = [3, 1, 2].into_iter().collect();
+        assert!(s.iter().copied().eq(1..=3));
+    }
+    #[test]
+    fn test_avl_hash() {
+        use std::collections::hash_map::DefaultHasher;
+
+        fn hash_of<T: Hash>(t: &T) -> u64 {
+            let mut s = DefaultHasher::new();
+            t.hash(&mut s);
+            s.finish()
+        }
+
+        let a = AVL::empty().put(1, "a").put(2, "b").put(3, "c");
+        let b = AVL::empty().put(3, "c").put(1, "a").put(2, "b");
+        assert_eq!(hash_of(&a), hash_of(&b));
+        assert_ne!(hash_of(&a), hash_of(&a.put(2, "z")));
+
+        let mut cache = std::collections::HashMap::new();
+        cache.insert(a, 42);
+        assert_eq!(cache.get(&b), Some(&42));
+    }
+    #[test]
+    fn test_avl_large() {
+        let n = 100_000;
+        let l = (0..n).fold(AVL::empty(), |t, i| t.put(i, i));
+        assert_eq!(l.len(), n as usize);
+        assert!(is_balanced(&l));
+        assert_eq!(l.find(&(n / 2)), Some(&(n / 2)));
+        let l = (0..n).step_by(2).fold(l, |t, i| t.delete(&i));
+        assert_eq!(l.len(), n as usize / 2);
+        assert!(is_balanced(&l));
+        assert!(l.find(&0).is_none());
+        assert_eq!(l.find(&1), Some(&1));
+    }
+    #[test]
+    fn test_avl_drop_deep_tree() {
+        // Deliberately unbalanced, to be far deeper than any real AVL
+        let mut t: AVL<i32> = AVL::Empty;
+        for i in 0..200_000 {
+            t = AVL::node(
+                RefCounter::new(i),
+                RefCounter::new(()),
+                RefCounter::new(t),
+                RefCounter::new(AVL::Empty),
+            );
+        }
+        drop(t);
+
+        let shared = (0..1000).fold(AVL::empty(), |t, i| t.insert(i));
+        let newer = shared.insert(1000);
+        drop(shared);
+        assert_eq!(newer.len(), 1001);
+        assert!(newer.keys().copied().eq(0..=1000));
+    }
+    #[test]
+    fn test_avl_double_ended_iter() {
+        let m: OrderedMap<_, _> = (0..50).map(|i| (i, i * 2)).collect();
+        assert!(m.keys().rev().copied().eq((0..50).rev()));
+        assert!(m.values().rev().copied().eq((0..50).rev().map(|i| i * 2)));
+
+        let mut iter = m.iter();
+        assert_eq!(iter.len(), 50);
+        assert_eq!(iter.next(), Some((&0, &0)));
+        assert_eq!(iter.next_back(), Some((&49, &98)));
+        let mut middle = Vec::new();
+        while let (Some(front), Some(back)) = (iter.next(), iter.next_back()) {
+            middle.push(*front.0);
+            middle.push(*back.0);
+        }
+        assert_eq!(middle.len(), 48);
+        assert!(iter.next().is_none() && iter.next_back().is_none());
+
+        let single = AVL::empty().put(1, ());
+        let mut iter = single.iter();
+        assert_eq!(iter.next_back(), Some((&1, &())));
+        assert!(iter.next().is_none());
+    }
+    #[test]
+    fn test_avl_into_iter() {
+        let m: OrderedMap<_, _> = (0..100).map(|i| (i, i.to_string())).collect();
+        let shared = m.clone();
+        let entries: Vec<(i32, String)> = m.into_iter().collect();
+        assert_eq!(entries.len(), 100);
+        assert!(entries.iter().map(|(k, _)| *k).eq(0..100));
+        assert_eq!(entries[42].1, "42");
+        assert_eq!(shared.len(), 100);
+        assert_eq!(shared.find(&42), Some(&"42".to_string()));
+
+        let mut total = 0;
+        for (k, v) in &shared {
+            total += k + v.len() as i32;
+        }
+        assert_eq!(total, 4950 + 190);
+    }
+
+    #[test]
+    fn test_avl_into_iter_moves_unique_values() {
+        #[derive(PartialEq, Debug)]
+        struct NoClone(i32);
+        impl Clone for NoClone {
+            fn clone(&self) -> Self {
+                panic!("value should have been moved out")
+            }
+        }
+
+        let m: OrderedMap<_, _> = (0..10).map(|i| (i, NoClone(i))).collect();
+        let values: Vec<_> = m.into_iter().map(|(_, v)| v.0).collect();
+        assert!(values.into_iter().eq(0..10));
+    }
+    #[test]
+    fn test_avl_custom_comparator() {
+        struct CaseInsensitive;
+        impl Compare<String> for CaseInsensitive {
+            fn compare(a: &String, b: &String) -> Ordering {
+                a.to_lowercase().cmp(&b.to_lowercase())
+            }
+        }
+
+        let m = AVL::<_, _, CaseInsensitive>::empty_ordered()
+            .put("Banana".to_string(), 2)
+            .put("apple".to_string(), 1)
+            .put("BANANA".to_string(), 3);
+        assert_eq!(m.len(), 2);
+        assert_eq!(m.find(&"banana".to_string()), Some(&3));
+        assert_eq!(m.min(), Some((&"apple".to_string(), &1)));
+
+        let reversed: AVL<_, (), Reverse> = (0..10).collect();
+        assert!(reversed.keys().copied().eq((0..10).rev()));
+        assert!(reversed.search(&3));
+        assert_eq!(reversed.rank(&9), 0);
+        let reversed = reversed.delete(&9).insert(42);
+        assert_eq!(reversed.min(), Some((&42, &())));
+        assert!(is_balanced(&reversed));
+    }
+    #[test]
+    fn test_avl_borrowed_lookup() {
+        let m = AVL::empty()
+            .put("apple".to_string(), 1)
+            .put("banana".to_string(), 2);
+        assert_eq!(m.find("apple"), Some(&1));
+        assert!(m.find("cherry").is_none());
+        let (removed, m2) = m.remove("banana").unwrap();
+        assert_eq!(*removed, 2);
+        assert_eq!(m2.len(), 1);
+        assert_eq!(m.delete("apple").len(), 1);
+
+        let s: AVL<Vec<u8>> = vec![b"ab".to_vec(), b"cd".to_vec()].into_iter().collect();
+        assert!(s.search(&b"ab"[..]));
+        assert!(!s.search(&b"zz"[..]));
+    }
+    #[test]
+    fn test_avl_count_range() {
+        let timestamps: AVL<_> = (0..100).map(|i| i * 10).collect();
+        assert_eq!(timestamps.count_range(100..200), 10);
+        assert_eq!(timestamps.count_range(100..=200), 11);
+        assert_eq!(timestamps.count_range(95..105), 1);
+        assert_eq!(timestamps.count_range(..50), 5);
+        assert_eq!(timestamps.count_range(985..), 1);
+        assert_eq!(timestamps.count_range(..), 100);
+        assert_eq!(
+            timestamps.count_range((Bound::Included(500), Bound::Excluded(100))),
+            0
+        );
+        assert_eq!(
+            timestamps.count_range((Bound::Excluded(100), Bound::Excluded(200))),
+            9
+        );
+    }
+    #[test]
+    fn test_avl_delete_range() {
+        let m: OrderedMap<i32, i32> = (0..100).map(|i| (i, i)).collect();
+        let d = m.delete_range(10..20);
+        assert_eq!(d.len(), 90);
+        assert!(d.find(&9).is_some() && d.find(&20).is_some());
+        assert!(d.find(&10).is_none() && d.find(&19).is_none());
+        assert!(is_balanced(&d));
+
+        assert!(m.delete_range(..50).keys().copied().eq(50..100));
+        assert!(m.delete_range(50..).keys().copied().eq(0..50));
+        assert!(m.delete_range(..).is_empty());
+        assert_eq!(m.delete_range(10..=20).len(), 89);
+        assert_eq!(m.delete_range(200..300).len(), 100);
+        assert_eq!(
+            m.delete_range((Bound::Included(50), Bound::Excluded(10)))
+                .len(),
+            100
+        );
+        assert_eq!(m.len(), 100);
+    }
+    #[test]
+    fn test_avl_key_value_accessors() {
+        let first = RefCounter::new(1);
+        let m = AVL::empty()
+            .put(first.clone(), "a")
+            .put(RefCounter::new(2), "b")
+            .put(RefCounter::new(3), "c");
+        let (key, value) = m.get_key_value(&1).unwrap();
+        assert!(RefCounter::ptr_eq(key, &first));
+        assert_eq!(*value, "a");
+        assert!(m.get_key_value(&4).is_none());
+        assert_eq!(m.first_key_value().map(|(k, v)| (**k, *v)), Some((1, "a")));
+        assert_eq!(m.last_key_value().map(|(k, v)| (**k, *v)), Some((3, "c")));
+        assert!(AVL::<i32, i32>::empty().first_key_value().is_none());
+    }
+    #[test]
+    fn test_avl_index() {
+        let m = AVL::empty()
+            .put("one".to_string(), 1)
+            .put("two".to_string(), 2);
+        assert_eq!(m["one"], 1);
+        assert_eq!(m[&"two".to_string()], 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "no entry found for key")]
+    fn test_avl_index_missing_key() {
+        let m = AVL::empty().put(1, 1);
+        let _ = m[&2];
+    }
+    #[test]
+    fn test_avl_put_with() {
+        let words = ["the", "cat", "the", "hat", "the"];
+        let counts = words
+            .iter()
+            .fold(AVL::empty(), |m, w| m.put_with(*w, 1, |old, new| old + new));
+        assert_eq!(counts.find(&"the"), Some(&3));
+        assert_eq!(counts.find(&"cat"), Some(&1));
+        assert_eq!(counts.len(), 3);
+        assert!(is_balanced(&counts));
+    }
+    #[test]
+    fn test_avl_next_prev() {
+        let m: OrderedMap<i32, i32> = (0..10).map(|i| (i * 10, i)).collect();
+        assert_eq!(m.next_after(&20), Some((&30, &3)));
+        assert_eq!(m.next_after(&25), Some((&30, &3)));
+        assert_eq!(m.next_after(&-5), Some((&0, &0)));
+        assert!(m.next_after(&90).is_none());
+        assert_eq!(m.prev_before(&20), Some((&10, &1)));
+        assert_eq!(m.prev_before(&25), Some((&20, &2)));
+        assert_eq!(m.prev_before(&1000), Some((&90, &9)));
+        assert!(m.prev_before(&0).is_none());
+
+        let mut page = Vec::new();
+        let mut cursor = m.first_key_value();
+        while let Some((k, _)) = cursor {
+            page.push(*k);
+            cursor = m.next_after(k);
+        }
+        assert!(page.into_iter().eq((0..10).map(|i| i * 10)));
+    }
+    #[test]
+    fn test_avl_put_owned() {
+        let built = (0..1000).fold(AVL::empty(), |t, i| t.put_owned(i, i));
+        assert_eq!(built.len(), 1000);
+        assert!(is_balanced(&built));
+        assert!(built.keys().copied().eq(0..1000));
+
+        let snapshot = built.clone();
+        let updated = built.put_owned(500, -1).put_owned(1000, 1000);
+        assert_eq!(updated.find(&500), Some(&-1));
+        assert_eq!(updated.len(), 1001);
+        assert_eq!(snapshot.find(&500), Some(&500));
+        assert_eq!(snapshot.len(), 1000);
+        assert!(is_balanced(&updated));
+
+        let set = (0..100).rev().fold(AVL::empty(), |s, i| s.insert_owned(i));
+        assert!(set.keys().copied().eq(0..100));
+    }
+    #[test]
+    fn test_avl_put_if_changed() {
+        fn same_root<K, V>(a: &AVL<K, V>, b: &AVL<K, V>) -> bool {
+            match (a, b) {
+                (
+                    AVL::Node {
+                        value: va,
+                        left: la,
+                        right: ra,
+                        ..
+                    },
+                    AVL::Node {
+                        value: vb,
+                        left: lb,
+                        right: rb,
+                        ..
+                    },
+                ) => {
+                    RefCounter::ptr_eq(va, vb)
+                        && RefCounter::ptr_eq(la, lb)
+                        && RefCounter::ptr_eq(ra, rb)
+                }
+                _ => false,
+            }
+        }
+        let t: OrderedMap<i32, i32> = (0..100).map(|i| (i, i)).collect();
+        let (same, changed) = t.put_if_changed(40, 40);
+        assert!(!changed);
+        assert!(same_root(&t, &same));
+        let (updated, changed) = t.put_if_changed(40, -40);
+        assert!(changed);
+        assert!(!same_root(&t, &updated));
+        assert_eq!(updated.find(&40), Some(&-40));
+        let (grown, changed) = t.put_if_changed(100, 100);
+        assert!(changed);
+        assert_eq!(grown.len(), 101);
+
+        let (stored, _) = t.remove(&40).unwrap();
+        assert!(same_root(&t, &t.put_shared(40, stored)));
+        let fresh = t.put_shared(40, RefCounter::new(40));
+        assert!(!same_root(&t, &fresh));
+        assert!(fresh == t);
+        assert_eq!(t.put_shared(100, RefCounter::new(100)).len(), 101);
+
+        let set: AVL<i32> = (0..100).collect();
+        assert!(same_root(&set, &set.insert(7)));
+        assert_eq!(set.insert(100).len(), 101);
+    }
+    #[test]
+    fn test_avl_extend() {
+        let base: OrderedMap<i32, i32> = (0..100).map(|i| (i, i)).collect();
+        let extended = base.extend((50..300).map(|i| (i, -i)));
+        assert_eq!(extended.len(), 300);
+        assert!(is_balanced(&extended));
+        assert_eq!(extended.find(&10), Some(&10));
+        assert_eq!(extended.find(&70), Some(&-70));
+        assert_eq!(extended.find(&299), Some(&-299));
+        assert_eq!(base.len(), 100);
+        assert_eq!(base.find(&70), Some(&70));
+        assert!(base.extend(std::iter::empty()) == base);
+    }
+    #[test]
+    fn test_avl_summary() {
+        #[derive(Clone)]
+        struct Total(i64);
+        impl<K> Summary<K, i64> for Total {
+            fn summarize(
+                _: &RefCounter<K>,
+                value: &RefCounter<i64>,
+                left: Option<&Total>,
+                right: Option<&Total>,
+            ) -> Total {
+                let children = [left, right].into_iter().flatten().map(|t| t.0);
+                Total(**value + children.sum::<i64>())
+            }
+        }
+        fn total(t: &AVL<i32, i64, Natural, Total>) -> i64 {
+            t.summary().map_or(0, |t| t.0)
+        }
+        let t: AVL<i32, i64, Natural, Total> = (0..100).map(|i| (i, i64::from(i))).collect();
+        assert_eq!(total(&t), 4950);
+        assert_eq!(total(&t.put(10, 0)), 4940);
+        assert_eq!(total(&t.put(100, 1)), 4951);
+        assert_eq!(total(&t.delete(&99)), 4851);
+        assert_eq!(total(&t.clone().put_owned(10, 0)), 4940);
+        assert_eq!(total(&t.map_values(|v| v * 2)), 9900);
+        assert_eq!(total(&t.delete_range(..50)), 3725);
+        assert_eq!(total(&AVL::Empty), 0);
+        assert_eq!(total(&t), 4950);
+    }
+    #[test]
+    fn test_avl_btreemap_conversions() {
+        let map: BTreeMap<i32, String> = (0..500).map(|i| (i, i.to_string())).collect();
+        let tree = AVL::from(map.clone());
+        assert_eq!(tree.len(), 500);
+        assert!(is_balanced(&tree));
+        assert_eq!(tree.find(&42).map(String::as_str), Some("42"));
+        let back: BTreeMap<_, _> = tree.put(500, "500".to_string()).into();
+        assert_eq!(back.len(), 501);
+        assert!(back.iter().take(500).eq(map.iter()));
+        assert!(BTreeMap::from(AVL::<i32, i32>::empty()).is_empty());
+    }
+    #[test]
+    fn test_avl_iter_from() {
+        let t: OrderedMap<i32, i32> = (0..100).map(|i| (i * 2, i)).collect();
+        assert!(t.iter_from(&50).map(|(k, _)| *k).eq((50..200).step_by(2)));
+        assert!(t.iter_from(&51).map(|(k, _)| *k).eq((52..200).step_by(2)));
+        assert_eq!(t.iter_from(&51).len(), 74);
+        assert_eq!(t.iter_from(&-5).len(), 100);
+        assert_eq!(t.iter_from(&199).next(), None);
+        assert_eq!(t.iter_from(&10).next_back(), Some((&198, &99)));
+        let window: Vec<_> = t.iter_from(&20).take(3).map(|(_, v)| *v).collect();
+        assert_eq!(window, vec![10, 11, 12]);
+    }
+    #[test]
+    fn test_avl_partition_point() {
+        let versions: AVL<u32> = (0..100).map(|i| i * 3).collect();
+        assert_eq!(versions.partition_point(|v| *v < 30), 10);
+        assert_eq!(versions.partition_point(|v| *v <= 30), 11);
+        assert_eq!(versions.partition_point(|_| true), 100);
+        assert_eq!(versions.partition_point(|_| false), 0);
+        assert_eq!(AVL::<u32>::empty().partition_point(|_| true), 0);
+
+        let (old, new) = versions.split_at_partition(|v| *v < 100);
+        assert!(old.keys().copied().eq((0..34).map(|i| i * 3)));
+        assert!(new.keys().copied().eq((34..100).map(|i| i * 3)));
+        assert!(is_balanced(&old) && is_balanced(&new));
+    }
+    #[test]
+    fn test_avl_merge_iter() {
+        let shard_a: OrderedMap<i32, &str> =
+            [(1, "a1"), (3, "a3"), (5, "a5")].into_iter().collect();
+        let shard_b: OrderedMap<i32, &str> =
+            [(2, "b2"), (3, "b3"), (6, "b6")].into_iter().collect();
+        let merged: Vec<_> = shard_a
+            .merge_iter(&shard_b, |_, _| Ordering::Less)
+            .collect();
+        assert_eq!(
+            merged,
+            vec![
+                (&1, &"a1"),
+                (&2, &"b2"),
+                (&3, &"a3"),
+                (&3, &"b3"),
+                (&5, &"a5"),
+                (&6, &"b6")
+            ]
+        );
+        let b_first: Vec<_> = shard_a
+            .merge_iter(&shard_b, |a, b| a.cmp(b).reverse())
+            .map(|(_, v)| *v)
+            .collect();
+        assert_eq!(b_first, vec!["a1", "b2", "b3", "a3", "a5", "b6"]);
+        assert_eq!(
+            shard_a
+                .merge_iter(&AVL::empty(), |_, _| Ordering::Equal)
+                .len(),
+            3
+        );
+    }
+    #[test]
+    fn test_avl_diff() {
+        let old: OrderedMap<i32, i32> = (0..1000).map(|i| (i, i)).collect();
+        let new = old.put(5, -5).delete(&500).put(2000, 2000);
+        let changes: Vec<_> = old.diff(&new).collect();
+        assert_eq!(
+            changes,
+            vec![
+                DiffEntry::Changed(&5, &5, &-5),
+                DiffEntry::Removed(&500, &500),
+                DiffEntry::Added(&2000, &2000),
+            ]
+        );
+        assert_eq!(old.diff(&old).count(), 0);
+        assert_eq!(old.diff(&old.put(7, 7)).count(), 0);
+
+        // Unrelated trees holding the same entries have no differences either
+        let rebuilt = (0..1000).fold(AVL::empty(), |t, i| t.put(i, i));
+        assert_eq!(old.diff(&rebuilt).count(), 0);
+        assert_eq!(AVL::empty().diff(&old).count(), 1000);
+        assert_eq!(old.diff(&AVL::empty()).count(), 1000);
+    }
+    #[test]
+    fn test_avl_validate() {
+        let m: OrderedMap<i32, i32> = (0..100).map(|i| (i, i)).collect();
+        assert_eq!(m.validate(), Ok(()));
+        assert_eq!(m.filter(|k, _| k % 3 == 0).validate(), Ok(()));
+        assert_eq!(AVL::<i32, i32>::empty().validate(), Ok(()));
+
+        let leaf = |k| {
+            RefCounter::new(AVL::node(
+                RefCounter::new(k),
+                RefCounter::new(()),
+                RefCounter::default(),
+                RefCounter::default(),
+            ))
+        };
+        let unordered: AVL<i32> =
+            AVL::node(RefCounter::new(1), RefCounter::new(()), leaf(5), leaf(9));
+        assert_eq!(
+            unordered.validate(),
+            Err(InvariantViolation::Unordered { index: 0 })
+        );
+
+        let chain: AVL<i32> = AVL::node(
+            RefCounter::new(1),
+            RefCounter::new(()),
+            RefCounter::default(),
+            RefCounter::new(AVL::node(
+                RefCounter::new(2),
+                RefCounter::new(()),
+                RefCounter::default(),
+                leaf(3),
+            )),
+        );
+        assert_eq!(
+            chain.validate(),
+            Err(InvariantViolation::Unbalanced { index: 0 })
+        );
+        assert_eq!(
+            chain.validate().unwrap_err().to_string(),
+            "node at index 0 is unbalanced"
+        );
+    }
+    #[test]
+    fn test_avl_cursor_navigation() {
+        let m: OrderedMap<i32, i32> = (0..50).map(|i| (i * 2, i)).collect();
+        let mut cursor = m.cursor_front();
+        let mut seen = Vec::new();
+        while let Some((k, _)) = cursor.peek() {
+            seen.push(*k);
+            cursor = cursor.move_next();
+        }
+        assert!(seen.into_iter().eq((0..50).map(|i| i * 2)));
+        let wrapped = cursor.move_next();
+        assert_eq!(wrapped.peek(), Some((&0, &0)));
+        assert!(wrapped.move_prev().peek().is_none());
+        assert_eq!(cursor.move_prev().peek(), Some((&98, &49)));
+        assert!(cursor.peek().is_none());
+
+        let mut cursor = m.cursor_back();
+        let mut seen = Vec::new();
+        while let Some((k, _)) = cursor.peek() {
+            seen.push(*k);
+            cursor = cursor.move_prev();
+        }
+        assert!(seen.into_iter().eq((0..50).rev().map(|i| i * 2)));
+
+        let cursor = m.cursor_at(&31);
+        assert_eq!(cursor.peek(), Some((&32, &16)));
+        assert_eq!(cursor.move_prev().peek(), Some((&30, &15)));
+        assert_eq!(cursor.peek(), Some((&32, &16)));
+        assert_eq!(m.cursor_at(&30).peek(), Some((&30, &15)));
+        assert!(m.cursor_at(&99).peek().is_none());
+        assert!(AVL::<i32, i32>::empty().cursor_front().peek().is_none());
+    }
+
+    #[test]
+    fn test_avl_cursor_edit() {
+        let m: OrderedMap<i32, i32> = (0..50).map(|i| (i * 2, i)).collect();
+        let cursor = m.cursor_at(&31);
+        let inserted = cursor.insert_before(31, -1).unwrap();
+        assert_eq!(inserted.find(&31), Some(&-1));
+        assert_eq!(inserted.len(), 51);
+        assert_eq!(inserted.validate(), Ok(()));
+        assert!(cursor.insert_before(29, 0).is_none());
+        assert!(cursor.insert_before(32, 0).is_none());
+
+        let removed = cursor.remove().unwrap();
+        assert!(removed.find(&32).is_none());
+        assert_eq!(removed.len(), 49);
+        assert_eq!(removed.validate(), Ok(()));
+
+        let end = m.cursor_at(&1000);
+        assert!(end.remove().is_none());
+        let appended = end.insert_before(1000, 500).unwrap();
+        assert_eq!(appended.max(), Some((&1000, &500)));
+        assert_eq!(appended.validate(), Ok(()));
+        assert!(end.insert_before(50, 0).is_none());
+
+        let front = m.cursor_front();
+        let prepended = front.insert_before(-1, 0).unwrap();
+        assert_eq!(prepended.min(), Some((&-1, &0)));
+        assert_eq!(prepended.validate(), Ok(()));
+
+        let empty = AVL::<i32, i32>::empty();
+        assert_eq!(empty.cursor_front().insert_before(1, 1).unwrap().len(), 1);
+        assert_eq!(m.len(), 50);
+    }
 }
@@ -0,0 +1,390 @@
+use std::marker::PhantomData;
+
+use crate::RefCounter;
+
+// Fixed-width integers that can be split into bits, most significant first
+pub trait BitKey: Copy {
+    const BITS: u32;
+    // The key moved to the top bits of a u128
+    fn to_bits(self) -> u128;
+    fn from_bits(bits: u128) -> Self;
+}
+
+macro_rules! bit_key {
+    ($($t:ty),*) => {
+        $(impl BitKey for $t {
+            const BITS: u32 = <$t>::BITS;
+            fn to_bits(self) -> u128 {
+                (self as u128) << (128 - Self::BITS)
+            }
+            fn from_bits(bits: u128) -> Self {
+                (bits >> (128 - Self::BITS)) as $t
+            }
+        })*
+    };
+}
+
+bit_key!(u8, u16, u32, u64, u128);
+
+// Binary trie over prefixes of integer keys, such as CIDR blocks. Every node
+// holds its whole prefix, so runs of bits shared by everything below a node
+// are skipped instead of taking one node per bit. A child extends the prefix
+// of its parent by at least one bit, and that first extra bit picks its side
+pub struct BitTrie<K, V> {
+    root: Option<RefCounter<BitNode<V>>>,
+    len: usize,
+    key: PhantomData<K>,
+}
+
+struct BitNode<V> {
+    bits: u128,
+    prefix_len: u32,
+    value: Option<RefCounter<V>>,
+    children: [Option<RefCounter<BitNode<V>>>; 2],
+}
+
+impl<V> Clone for BitNode<V> {
+    fn clone(&self) -> Self {
+        Self {
+            bits: self.bits,
+            prefix_len: self.prefix_len,
+            value: self.value.clone(),
+            children: self.children.clone(),
+        }
+    }
+}
+
+impl<K, V> Clone for BitTrie<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root.clone(),
+            len: self.len,
+            key: PhantomData,
+        }
+    }
+}
+
+impl<K: BitKey, V> Default for BitTrie<K, V> {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+// Keeps the first prefix_len bits
+fn mask(bits: u128, prefix_len: u32) -> u128 {
+    match prefix_len {
+        0 => 0,
+        _ => bits & (u128::MAX << (128 - prefix_len)),
+    }
+}
+
+fn bit_at(bits: u128, index: u32) -> usize {
+    ((bits >> (127 - index)) & 1) as usize
+}
+
+fn common_len(a: u128, a_len: u32, b: u128, b_len: u32) -> u32 {
+    (a ^ b).leading_zeros().min(a_len).min(b_len)
+}
+
+impl<V> BitNode<V> {
+    fn leaf(bits: u128, prefix_len: u32, value: RefCounter<V>) -> Self {
+        BitNode {
+            bits,
+            prefix_len,
+            value: Some(value),
+            children: [None, None],
+        }
+    }
+    // Whether the prefix of the node covers the given prefix
+    fn covers(&self, bits: u128, prefix_len: u32) -> bool {
+        self.prefix_len <= prefix_len && mask(bits, self.prefix_len) == self.bits
+    }
+    fn insert(
+        node: &Option<RefCounter<BitNode<V>>>,
+        bits: u128,
+        prefix_len: u32,
+        value: RefCounter<V>,
+    ) -> BitNode<V> {
+        let Some(node) = node else {
+            return BitNode::leaf(bits, prefix_len, value);
+        };
+        let common = common_len(node.bits, node.prefix_len, bits, prefix_len);
+        if common == node.prefix_len && common == prefix_len {
+            let mut new_node = node.as_ref().clone();
+            new_node.value = Some(value);
+            new_node
+        } else if common == node.prefix_len {
+            let side = bit_at(bits, common);
+            let mut new_node = node.as_ref().clone();
+            new_node.children[side] = Some(RefCounter::new(BitNode::insert(
+                &node.children[side],
+                bits,
+                prefix_len,
+                value,
+            )));
+            new_node
+        } else if common == prefix_len {
+            // The new prefix sits above the node
+            let mut new_node = BitNode::leaf(bits, prefix_len, value);
+            new_node.children[bit_at(node.bits, common)] = Some(node.clone());
+            new_node
+        } else {
+            // Both continue past their common bits, on different sides
+            let side = bit_at(bits, common);
+            let mut fork = BitNode {
+                bits: mask(bits, common),
+                prefix_len: common,
+                value: None,
+                children: [None, None],
+            };
+            fork.children[side] = Some(RefCounter::new(BitNode::leaf(bits, prefix_len, value)));
+            fork.children[1 - side] = Some(node.clone());
+            fork
+        }
+    }
+    // None when the prefix was not stored. Otherwise the node that replaces
+    // this one, which is None when nothing is left under it
+    fn delete(
+        node: &RefCounter<BitNode<V>>,
+        bits: u128,
+        prefix_len: u32,
+    ) -> Option<Option<RefCounter<BitNode<V>>>> {
+        if !node.covers(bits, prefix_len) {
+            return None;
+        }
+        let mut new_node = node.as_ref().clone();
+        if node.prefix_len == prefix_len {
+            new_node.value.take()?;
+        } else {
+            let side = bit_at(bits, node.prefix_len);
+            new_node.children[side] =
+                BitNode::delete(node.children[side].as_ref()?, bits, prefix_len)?;
+        }
+        if new_node.value.is_some() {
+            return Some(Some(RefCounter::new(new_node)));
+        }
+        // A node without a value is only kept while it joins two children
+        Some(match new_node.children.clone() {
+            [None, None] => None,
+            [Some(child), None] | [None, Some(child)] => Some(child),
+            [Some(_), Some(_)] => Some(RefCounter::new(new_node)),
+        })
+    }
+}
+
+impl<K: BitKey, V> BitTrie<K, V> {
+    pub fn empty() -> Self {
+        BitTrie {
+            root: None,
+            len: 0,
+            key: PhantomData,
+        }
+    }
+    // Number of stored prefixes
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    fn prefix_bits(key: K, prefix_len: u32) -> u128 {
+        assert!(prefix_len <= K::BITS, "prefix longer than the key");
+        mask(key.to_bits(), prefix_len)
+    }
+    // Stores the value for the first prefix_len bits of the key, replacing
+    // the value stored for that prefix. Bits past the prefix are ignored
+    pub fn insert(&self, key: K, prefix_len: u32, value: V) -> Self {
+        let bits = Self::prefix_bits(key, prefix_len);
+        let is_new = self.get(key, prefix_len).is_none();
+        BitTrie {
+            root: Some(RefCounter::new(BitNode::insert(
+                &self.root,
+                bits,
+                prefix_len,
+                RefCounter::new(value),
+            ))),
+            len: self.len + usize::from(is_new),
+            key: PhantomData,
+        }
+    }
+    // Value stored for exactly this prefix
+    pub fn get(&self, key: K, prefix_len: u32) -> Option<&V> {
+        let bits = Self::prefix_bits(key, prefix_len);
+        let mut node = self.root.as_ref();
+        while let Some(current) = node {
+            if !current.covers(bits, prefix_len) {
+                return None;
+            }
+            if current.prefix_len == prefix_len {
+                return current.value.as_deref();
+            }
+            node = current.children[bit_at(bits, current.prefix_len)].as_ref();
+        }
+        None
+    }
+    pub fn delete(&self, key: K, prefix_len: u32) -> Option<Self> {
+        let bits = Self::prefix_bits(key, prefix_len);
+        let root = BitNode::delete(self.root.as_ref()?, bits, prefix_len)?;
+        Some(BitTrie {
+            root,
+            len: self.len - 1,
+            key: PhantomData,
+        })
+    }
+    // Longest stored prefix that contains the whole key, as in a routing
+    // table lookup
+    pub fn longest_match(&self, key: K) -> Option<(K, u32, &V)> {
+        let bits = key.to_bits();
+        let mut node = self.root.as_ref();
+        let mut best = None;
+        while let Some(current) = node {
+            if !current.covers(bits, K::BITS) {
+                break;
+            }
+            if let Some(value) = &current.value {
+                best = Some((
+                    K::from_bits(current.bits),
+                    current.prefix_len,
+                    value.as_ref(),
+                ));
+            }
+            if current.prefix_len == K::BITS {
+                break;
+            }
+            node = current.children[bit_at(bits, current.prefix_len)].as_ref();
+        }
+        best
+    }
+    // Stored prefixes inside the given one, itself included
+    pub fn within(&self, key: K, prefix_len: u32) -> BitTrieIterator<'_, K, V> {
+        let bits = Self::prefix_bits(key, prefix_len);
+        let mut node = self.root.as_ref();
+        while let Some(current) = node {
+            if current.prefix_len >= prefix_len {
+                break;
+            }
+            if !current.covers(bits, prefix_len) {
+                node = None;
+                break;
+            }
+            node = current.children[bit_at(bits, current.prefix_len)].as_ref();
+        }
+        let start = node.filter(|current| mask(current.bits, prefix_len) == bits);
+        BitTrieIterator {
+            stack: start.map(|current| current.as_ref()).into_iter().collect(),
+            key: PhantomData,
+        }
+    }
+    // Prefixes in order of their bits, a prefix before the longer ones
+    // inside it
+    pub fn iter(&self) -> BitTrieIterator<'_, K, V> {
+        BitTrieIterator {
+            stack: self.root.as_deref().into_iter().collect(),
+            key: PhantomData,
+        }
+    }
+}
+
+pub struct BitTrieIterator<'a, K, V> {
+    stack: Vec<&'a BitNode<V>>,
+    key: PhantomData<K>,
+}
+
+impl<'a, K: BitKey, V> Iterator for BitTrieIterator<'a, K, V> {
+    type Item = (K, u32, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let node = self.stack.pop()?;
+            self.stack.extend(
+                node.children
+                    .iter()
+                    .rev()
+                    .flatten()
+                    .map(|child| child.as_ref()),
+            );
+            if let Some(value) = &node.value {
+                return Some((K::from_bits(node.bits), node.prefix_len, value.as_ref()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ipv4(a: u8, b: u8, c: u8, d: u8) -> u32 {
+        u32::from_be_bytes([a, b, c, d])
+    }
+
+    #[test]
+    fn test_bit_trie_routing_table() {
+        let table = BitTrie::empty()
+            .insert(ipv4(0, 0, 0, 0), 0, "default")
+            .insert(ipv4(10, 0, 0, 0), 8, "private")
+            .insert(ipv4(10, 1, 0, 0), 16, "office")
+            .insert(ipv4(10, 1, 2, 0), 24, "lab")
+            .insert(ipv4(192, 168, 1, 0), 24, "home");
+        assert_eq!(table.len(), 5);
+        let route = |addr| {
+            table
+                .longest_match(addr)
+                .map(|(_, len, value)| (len, *value))
+        };
+        assert_eq!(route(ipv4(10, 1, 2, 3)), Some((24, "lab")));
+        assert_eq!(route(ipv4(10, 1, 9, 9)), Some((16, "office")));
+        assert_eq!(route(ipv4(10, 200, 0, 1)), Some((8, "private")));
+        assert_eq!(route(ipv4(8, 8, 8, 8)), Some((0, "default")));
+        assert_eq!(table.get(ipv4(10, 1, 255, 255), 16), Some(&"office"));
+        assert!(table.get(ipv4(10, 1, 0, 0), 17).is_none());
+
+        let inside: Vec<_> = table
+            .within(ipv4(10, 0, 0, 0), 8)
+            .map(|(addr, len, _)| (addr.to_be_bytes(), len))
+            .collect();
+        assert_eq!(
+            inside,
+            vec![([10, 0, 0, 0], 8), ([10, 1, 0, 0], 16), ([10, 1, 2, 0], 24)]
+        );
+        assert_eq!(table.within(ipv4(10, 1, 0, 0), 15).count(), 2);
+        assert_eq!(table.within(ipv4(11, 0, 0, 0), 8).count(), 0);
+        assert_eq!(table.within(0, 0).count(), 5);
+
+        let without_office = table.delete(ipv4(10, 1, 0, 0), 16).unwrap();
+        assert_eq!(without_office.len(), 4);
+        assert_eq!(
+            without_office
+                .longest_match(ipv4(10, 1, 9, 9))
+                .map(|(_, len, _)| len),
+            Some(8)
+        );
+        assert!(without_office.delete(ipv4(10, 1, 0, 0), 16).is_none());
+        assert_eq!(table.iter().count(), 5);
+    }
+
+    #[test]
+    fn test_bit_trie_replace_and_order() {
+        let t = BitTrie::empty()
+            .insert(u128::MAX, 128, 1)
+            .insert(0u128, 128, 2)
+            .insert(1u128 << 127, 1, 3)
+            .insert(u128::MAX, 128, 4);
+        assert_eq!(t.len(), 3);
+        assert_eq!(t.get(u128::MAX, 128), Some(&4));
+        let entries: Vec<_> = t
+            .iter()
+            .map(|(key, len, value)| (key, len, *value))
+            .collect();
+        assert_eq!(
+            entries,
+            vec![(0, 128, 2), (1 << 127, 1, 3), (u128::MAX, 128, 4)]
+        );
+        let emptied = t
+            .delete(0, 128)
+            .and_then(|t| t.delete(u128::MAX, 128))
+            .and_then(|t| t.delete(1 << 127, 1))
+            .unwrap();
+        assert!(emptied.is_empty() && emptied.iter().next().is_none());
+    }
+}
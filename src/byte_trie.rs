@@ -0,0 +1,214 @@
+use std::slice;
+
+use crate::RefCounter;
+
+// Trie over bytes where each node marks its children in a 256-bit bitmap and
+// keeps them densely, ordered by byte. The slot of a child is the number of
+// set bits below its byte
+pub struct ByteTrie<U = bool> {
+    stored_value: Vec<RefCounter<U>>,
+    bitmap: [u64; 4],
+    children: Vec<RefCounter<ByteTrie<U>>>,
+}
+
+impl<U> Clone for ByteTrie<U> {
+    fn clone(&self) -> Self {
+        Self {
+            stored_value: self.stored_value.clone(),
+            bitmap: self.bitmap,
+            children: self.children.clone(),
+        }
+    }
+}
+
+impl<U> Default for ByteTrie<U> {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<U> ByteTrie<U> {
+    pub fn empty() -> ByteTrie<U> {
+        ByteTrie {
+            stored_value: Vec::new(),
+            bitmap: [0; 4],
+            children: Vec::new(),
+        }
+    }
+    // Ok with the slot of the child for the byte, or Err with the slot it
+    // would be inserted at
+    fn slot(&self, byte: u8) -> Result<usize, usize> {
+        let (word, bit) = ((byte / 64) as usize, byte % 64);
+        let below: u32 = self.bitmap[..word]
+            .iter()
+            .map(|w| w.count_ones())
+            .sum::<u32>()
+            + (self.bitmap[word] & ((1u64 << bit) - 1)).count_ones();
+        if self.bitmap[word] & (1u64 << bit) != 0 {
+            Ok(below as usize)
+        } else {
+            Err(below as usize)
+        }
+    }
+    fn child(&self, byte: u8) -> Option<&ByteTrie<U>> {
+        self.slot(byte)
+            .ok()
+            .map(|index| self.children[index].as_ref())
+    }
+    fn bytes(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..=255u8).filter(|byte| self.bitmap[(byte / 64) as usize] & (1u64 << (byte % 64)) != 0)
+    }
+    pub fn insert_store<Slc: AsRef<[u8]>>(&self, key: Slc, store: U) -> Self {
+        let key = key.as_ref();
+        let mut new_trie = self.clone();
+        match key.split_first() {
+            None => new_trie.stored_value.push(RefCounter::new(store)),
+            Some((&head, tail)) => match self.slot(head) {
+                Ok(index) => {
+                    new_trie.children[index] =
+                        RefCounter::new(self.children[index].insert_store(tail, store));
+                }
+                Err(index) => {
+                    new_trie.bitmap[(head / 64) as usize] |= 1u64 << (head % 64);
+                    new_trie.children.insert(
+                        index,
+                        RefCounter::new(ByteTrie::empty().insert_store(tail, store)),
+                    );
+                }
+            },
+        }
+        new_trie
+    }
+    pub fn get_store<Slc: AsRef<[u8]>>(&self, key: Slc) -> Option<Box<[&U]>> {
+        let mut node = self;
+        for &byte in key.as_ref() {
+            node = node.child(byte)?;
+        }
+        if node.stored_value.is_empty() {
+            return None;
+        }
+        Some(node.stored_value.iter().map(|v| v.as_ref()).collect())
+    }
+    // Keys come out in byte order
+    pub fn iter(&self) -> ByteTrieIterator<'_, U> {
+        ByteTrieIterator {
+            stack: vec![(Vec::new(), self)],
+            current: None,
+        }
+    }
+}
+
+impl<U: PartialEq> ByteTrie<U> {
+    pub fn delete_store<Slc: AsRef<[u8]>>(&self, key: Slc, store: &U) -> Option<Self> {
+        let key = key.as_ref();
+        let mut new_trie = self.clone();
+        match key.split_first() {
+            None => {
+                new_trie.stored_value.retain(|v| v.as_ref() != store);
+                if new_trie.stored_value.len() == self.stored_value.len() {
+                    return None;
+                }
+            }
+            Some((&head, tail)) => {
+                let index = self.slot(head).ok()?;
+                let child = self.children[index].delete_store(tail, store)?;
+                if child.stored_value.is_empty() && child.children.is_empty() {
+                    new_trie.bitmap[(head / 64) as usize] &= !(1u64 << (head % 64));
+                    new_trie.children.remove(index);
+                } else {
+                    new_trie.children[index] = RefCounter::new(child);
+                }
+            }
+        }
+        Some(new_trie)
+    }
+}
+
+impl ByteTrie {
+    pub fn insert<Slc: AsRef<[u8]>>(&self, key: Slc) -> Self {
+        self.insert_store(key, true)
+    }
+    pub fn search<Slc: AsRef<[u8]>>(&self, key: Slc) -> bool {
+        self.get_store(key).is_some()
+    }
+    pub fn delete<Slc: AsRef<[u8]>>(&self, key: Slc) -> Option<Self> {
+        self.delete_store(key, &true)
+    }
+}
+
+pub struct ByteTrieIterator<'a, U> {
+    stack: Vec<(Vec<u8>, &'a ByteTrie<U>)>,
+    current: Option<(Vec<u8>, slice::Iter<'a, RefCounter<U>>)>,
+}
+
+impl<'a, U> Iterator for ByteTrieIterator<'a, U> {
+    type Item = (Vec<u8>, &'a U);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((key, values)) = &mut self.current {
+                if let Some(value) = values.next() {
+                    return Some((key.clone(), value.as_ref()));
+                }
+            }
+            let (key, node) = self.stack.pop()?;
+            let edges: Vec<_> = node.bytes().zip(node.children.iter()).collect();
+            for (byte, child) in edges.into_iter().rev() {
+                let mut child_key = key.clone();
+                child_key.push(byte);
+                self.stack.push((child_key, child));
+            }
+            self.current = Some((key, node.stored_value.iter()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_trie_insert_search_delete() {
+        let t = ByteTrie::empty()
+            .insert("route/a")
+            .insert("route/b")
+            .insert([0u8, 255, 128])
+            .insert("");
+        assert!(t.search("route/a"));
+        assert!(t.search([0u8, 255, 128]));
+        assert!(t.search(""));
+        assert!(!t.search("route/"));
+        assert!(!t.search([0u8, 255]));
+
+        let removed = t.delete("route/a").unwrap();
+        assert!(!removed.search("route/a"));
+        assert!(removed.search("route/b"));
+        assert!(t.search("route/a"));
+        assert!(removed.delete("route/a").is_none());
+
+        let pruned = removed.delete("route/b").unwrap();
+        assert!(pruned.child(b'r').is_none());
+    }
+
+    #[test]
+    fn test_byte_trie_store_and_iter() {
+        let t = ByteTrie::empty()
+            .insert_store([200u8], 1)
+            .insert_store([3u8, 64], 2)
+            .insert_store([3u8], 3)
+            .insert_store([3u8], 4)
+            .insert_store([63u8], 5);
+        assert_eq!(&*t.get_store([3u8]).unwrap(), &[&3, &4]);
+        let entries: Vec<_> = t.iter().map(|(key, value)| (key, *value)).collect();
+        assert_eq!(
+            entries,
+            vec![
+                (vec![3], 3),
+                (vec![3], 4),
+                (vec![3, 64], 2),
+                (vec![63], 5),
+                (vec![200], 1)
+            ]
+        );
+    }
+}
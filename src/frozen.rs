@@ -0,0 +1,189 @@
+use std::{borrow::Borrow, fmt::Debug, marker::PhantomData, slice};
+
+use crate::{
+    avl::{Compare, Natural, AVL},
+    RefCounter,
+};
+
+// Read-only map stored as one sorted slice. Lookups are binary searches and
+// clones only bump the reference count
+pub struct FrozenMap<K, V, C = Natural> {
+    entries: RefCounter<[(K, V)]>,
+    ordering: PhantomData<C>,
+}
+
+impl<K, V, C> Clone for FrozenMap<K, V, C> {
+    fn clone(&self) -> Self {
+        Self {
+            entries: self.entries.clone(),
+            ordering: PhantomData,
+        }
+    }
+}
+
+impl<K: Debug, V: Debug, C> Debug for FrozenMap<K, V, C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V, C> FrozenMap<K, V, C> {
+    // The entries must already be sorted by C without repeated keys
+    pub(crate) fn from_sorted_vec(entries: Vec<(K, V)>) -> Self {
+        Self {
+            entries: entries.into(),
+            ordering: PhantomData,
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    pub fn iter(&self) -> FrozenIterator<'_, K, V> {
+        FrozenIterator {
+            inner: self.entries.iter(),
+        }
+    }
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &K> + ExactSizeIterator {
+        self.entries.iter().map(|(key, _)| key)
+    }
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = &V> + ExactSizeIterator {
+        self.entries.iter().map(|(_, value)| value)
+    }
+    pub fn nth(&self, index: usize) -> Option<(&K, &V)> {
+        self.entries.get(index).map(|(key, value)| (key, value))
+    }
+    pub fn min(&self) -> Option<(&K, &V)> {
+        self.entries.first().map(|(key, value)| (key, value))
+    }
+    pub fn max(&self) -> Option<(&K, &V)> {
+        self.entries.last().map(|(key, value)| (key, value))
+    }
+}
+
+impl<K, V, C: Compare<K>> FrozenMap<K, V, C> {
+    pub fn find<Q: ?Sized>(&self, target_key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.get_key_value(target_key).map(|(_, value)| value)
+    }
+    pub fn get_key_value<Q: ?Sized>(&self, target_key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let index = self.search(target_key).ok()?;
+        self.nth(index)
+    }
+    pub fn rank<Q: ?Sized>(&self, target_key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        match self.search(target_key) {
+            Ok(index) | Err(index) => index,
+        }
+    }
+    pub fn iter_from<Q: ?Sized>(&self, bound: &Q) -> FrozenIterator<'_, K, V>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        FrozenIterator {
+            inner: self.entries[self.rank(bound)..].iter(),
+        }
+    }
+    // Back to a tree that can be updated again, built already balanced
+    pub fn thaw(&self) -> AVL<K, V, C>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        AVL::from_sorted_entries(self.entries.iter().cloned())
+    }
+    fn search<Q: ?Sized>(&self, target_key: &Q) -> Result<usize, usize>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.entries
+            .binary_search_by(|(key, _)| C::compare(key.borrow(), target_key))
+    }
+}
+
+pub struct FrozenIterator<'a, K, V> {
+    inner: slice::Iter<'a, (K, V)>,
+}
+
+impl<'a, K, V> Iterator for FrozenIterator<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(key, value)| (key, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for FrozenIterator<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(key, value)| (key, value))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for FrozenIterator<'a, K, V> {}
+
+impl<'a, K, V, C> IntoIterator for &'a FrozenMap<K, V, C> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = FrozenIterator<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::avl::{OrderedMap, Reverse};
+
+    #[test]
+    fn test_frozen_map_lookups() {
+        let tree: OrderedMap<i32, String> = (0..100).map(|i| (i * 2, i.to_string())).collect();
+        let frozen = tree.freeze();
+        assert_eq!(frozen.len(), 100);
+        assert_eq!(frozen.find(&42).map(String::as_str), Some("21"));
+        assert!(frozen.find(&43).is_none());
+        assert_eq!(frozen.rank(&43), 22);
+        assert_eq!(frozen.nth(3), Some((&6, &"3".to_string())));
+        assert_eq!(frozen.min().map(|(k, _)| *k), Some(0));
+        assert_eq!(frozen.max().map(|(k, _)| *k), Some(198));
+        assert!(frozen.keys().copied().eq((0..100).map(|i| i * 2)));
+        assert!(frozen
+            .iter_from(&191)
+            .map(|(k, _)| *k)
+            .eq([192, 194, 196, 198]));
+        assert_eq!(frozen.iter().next_back().map(|(k, _)| *k), Some(198));
+
+        let copy = frozen.clone();
+        assert_eq!(copy.find(&0).map(String::as_str), Some("0"));
+    }
+
+    #[test]
+    fn test_frozen_map_thaw() {
+        let tree: OrderedMap<_, _, Reverse> = (0..50).map(|i| (i, i)).collect();
+        let frozen = tree.freeze();
+        assert_eq!(frozen.find(&10), Some(&10));
+        assert!(frozen.keys().copied().eq((0..50).rev()));
+
+        let thawed = frozen.thaw().put(50, 50);
+        assert_eq!(thawed.validate(), Ok(()));
+        assert!(thawed.keys().copied().eq((0..=50).rev()));
+        assert_eq!(frozen.len(), 50);
+    }
+}
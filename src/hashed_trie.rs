@@ -0,0 +1,239 @@
+use std::{collections::HashMap, hash::Hash, slice};
+
+use crate::RefCounter;
+
+// Trie for large alphabets, such as token ids. A node keeps its children in
+// a Vec while there are few of them and moves them to a hash table once the
+// fan-out grows past MAX_FEW, so a lookup never scans thousands of children
+pub struct HashedTrie<T, U = bool> {
+    stored_value: Vec<RefCounter<U>>,
+    children: Children<T, U>,
+}
+
+enum Children<T, U> {
+    Few(Vec<(T, RefCounter<HashedTrie<T, U>>)>),
+    Many(HashMap<T, RefCounter<HashedTrie<T, U>>>),
+}
+
+const MAX_FEW: usize = 16;
+// Going back to a Vec only below this size keeps a node that hovers around
+// MAX_FEW from switching on every update
+const MIN_MANY: usize = MAX_FEW / 2;
+
+impl<T: Clone, U> Clone for Children<T, U> {
+    fn clone(&self) -> Self {
+        match self {
+            Children::Few(children) => Children::Few(children.clone()),
+            Children::Many(children) => Children::Many(children.clone()),
+        }
+    }
+}
+
+impl<T: Clone, U> Clone for HashedTrie<T, U> {
+    fn clone(&self) -> Self {
+        Self {
+            stored_value: self.stored_value.clone(),
+            children: self.children.clone(),
+        }
+    }
+}
+
+impl<T: Hash + Eq + Clone, U> Default for HashedTrie<T, U> {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<T: Hash + Eq + Clone, U> Children<T, U> {
+    fn get(&self, element: &T) -> Option<&RefCounter<HashedTrie<T, U>>> {
+        match self {
+            Children::Few(children) => children
+                .iter()
+                .find(|(key, _)| key == element)
+                .map(|(_, child)| child),
+            Children::Many(children) => children.get(element),
+        }
+    }
+    fn len(&self) -> usize {
+        match self {
+            Children::Few(children) => children.len(),
+            Children::Many(children) => children.len(),
+        }
+    }
+    fn set(&mut self, element: T, child: RefCounter<HashedTrie<T, U>>) {
+        match self {
+            Children::Few(children) => {
+                match children.iter_mut().find(|(key, _)| *key == element) {
+                    Some((_, existing)) => *existing = child,
+                    None => children.push((element, child)),
+                }
+                if children.len() > MAX_FEW {
+                    *self = Children::Many(children.drain(..).collect());
+                }
+            }
+            Children::Many(children) => {
+                children.insert(element, child);
+            }
+        }
+    }
+    fn remove(&mut self, element: &T) {
+        match self {
+            Children::Few(children) => children.retain(|(key, _)| key != element),
+            Children::Many(children) => {
+                children.remove(element);
+                if children.len() < MIN_MANY {
+                    *self = Children::Few(children.drain().collect());
+                }
+            }
+        }
+    }
+    fn iter(&self) -> Vec<(&T, &RefCounter<HashedTrie<T, U>>)> {
+        match self {
+            Children::Few(children) => children.iter().map(|(key, child)| (key, child)).collect(),
+            Children::Many(children) => children.iter().collect(),
+        }
+    }
+}
+
+impl<T: Hash + Eq + Clone, U> HashedTrie<T, U> {
+    pub fn empty() -> Self {
+        Self {
+            stored_value: Vec::new(),
+            children: Children::Few(Vec::new()),
+        }
+    }
+    pub fn insert_store<Slc: AsRef<[T]>>(&self, key: Slc, store: U) -> Self {
+        let key = key.as_ref();
+        let mut new_trie = self.clone();
+        match key.split_first() {
+            None => new_trie.stored_value.push(RefCounter::new(store)),
+            Some((head, tail)) => {
+                let child = match self.children.get(head) {
+                    Some(child) => child.insert_store(tail, store),
+                    None => HashedTrie::empty().insert_store(tail, store),
+                };
+                new_trie.children.set(head.clone(), RefCounter::new(child));
+            }
+        }
+        new_trie
+    }
+    pub fn get_store<Slc: AsRef<[T]>>(&self, key: Slc) -> Option<Box<[&U]>> {
+        let mut node = self;
+        for element in key.as_ref() {
+            node = node.children.get(element)?;
+        }
+        if node.stored_value.is_empty() {
+            return None;
+        }
+        Some(node.stored_value.iter().map(|v| v.as_ref()).collect())
+    }
+    // Children of nodes past MAX_FEW come out in no particular order
+    pub fn iter(&self) -> HashedTrieIterator<'_, T, U> {
+        HashedTrieIterator {
+            stack: vec![(Vec::new(), self)],
+            current: None,
+        }
+    }
+}
+
+impl<T: Hash + Eq + Clone, U: PartialEq> HashedTrie<T, U> {
+    pub fn delete_store<Slc: AsRef<[T]>>(&self, key: Slc, store: &U) -> Option<Self> {
+        let key = key.as_ref();
+        let mut new_trie = self.clone();
+        match key.split_first() {
+            None => {
+                new_trie.stored_value.retain(|v| v.as_ref() != store);
+                if new_trie.stored_value.len() == self.stored_value.len() {
+                    return None;
+                }
+            }
+            Some((head, tail)) => {
+                let child = self.children.get(head)?.delete_store(tail, store)?;
+                if child.stored_value.is_empty() && child.children.len() == 0 {
+                    new_trie.children.remove(head);
+                } else {
+                    new_trie.children.set(head.clone(), RefCounter::new(child));
+                }
+            }
+        }
+        Some(new_trie)
+    }
+}
+
+impl<T: Hash + Eq + Clone> HashedTrie<T> {
+    pub fn insert<Slc: AsRef<[T]>>(&self, key: Slc) -> Self {
+        self.insert_store(key, true)
+    }
+    pub fn search<Slc: AsRef<[T]>>(&self, key: Slc) -> bool {
+        self.get_store(key).is_some()
+    }
+    pub fn delete<Slc: AsRef<[T]>>(&self, key: Slc) -> Option<Self> {
+        self.delete_store(key, &true)
+    }
+}
+
+pub struct HashedTrieIterator<'a, T, U> {
+    stack: Vec<(Vec<T>, &'a HashedTrie<T, U>)>,
+    current: Option<(Vec<T>, slice::Iter<'a, RefCounter<U>>)>,
+}
+
+impl<'a, T: Hash + Eq + Clone, U> Iterator for HashedTrieIterator<'a, T, U> {
+    type Item = (Vec<T>, &'a U);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((key, values)) = &mut self.current {
+                if let Some(value) = values.next() {
+                    return Some((key.clone(), value.as_ref()));
+                }
+            }
+            let (key, node) = self.stack.pop()?;
+            for (element, child) in node.children.iter().into_iter().rev() {
+                let mut child_key = key.clone();
+                child_key.push(element.clone());
+                self.stack.push((child_key, child));
+            }
+            self.current = Some((key, node.stored_value.iter()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hashed_trie_wide_nodes() {
+        let tokens: Vec<[u32; 2]> = (0..1000).map(|id| [id, id * 7]).collect();
+        let t = tokens
+            .iter()
+            .fold(HashedTrie::empty(), |t, key| t.insert_store(key, key[0]));
+        assert!(matches!(t.children, Children::Many(_)));
+        for key in &tokens {
+            assert_eq!(&*t.get_store(key).unwrap(), &[&key[0]]);
+        }
+        assert!(t.get_store([5u32, 6]).is_none());
+        assert!(t.get_store([5u32]).is_none());
+        assert_eq!(t.iter().count(), 1000);
+
+        let shrunk = tokens[..995]
+            .iter()
+            .fold(t.clone(), |t, key| t.delete_store(key, &key[0]).unwrap());
+        assert!(matches!(shrunk.children, Children::Few(_)));
+        let left: Vec<_> = shrunk.iter().map(|(_, value)| *value).collect();
+        assert_eq!(left.len(), 5);
+        assert!(left.iter().all(|id| *id >= 995));
+        assert_eq!(t.iter().count(), 1000);
+    }
+
+    #[test]
+    fn test_hashed_trie_set() {
+        let t = HashedTrie::empty().insert([1u32, 2, 3]).insert([1u32, 2]);
+        assert!(t.search([1u32, 2, 3]) && t.search([1u32, 2]));
+        assert!(!t.search([1u32]));
+        let removed = t.delete([1u32, 2, 3]).unwrap();
+        assert!(!removed.search([1u32, 2, 3]));
+        assert!(removed.search([1u32, 2]));
+        assert!(removed.delete([9u32]).is_none());
+    }
+}
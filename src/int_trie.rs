@@ -0,0 +1,425 @@
+use crate::RefCounter;
+
+// Big-endian Patricia trie over u64 keys. A branch stores the bits its keys
+// share above the highest bit where they differ, and that bit splits them
+// into a left (bit unset) and a right (bit set) side, so walking left before
+// right gives the keys in increasing order. No operation goes deeper than
+// the 64 bits of a key
+#[derive(Default)]
+pub enum IntTrie<V> {
+    #[default]
+    Empty,
+    Leaf {
+        key: u64,
+        value: RefCounter<V>,
+    },
+    Branch {
+        prefix: u64,
+        mask: u64,
+        left: RefCounter<IntTrie<V>>,
+        right: RefCounter<IntTrie<V>>,
+        size: usize,
+    },
+}
+
+impl<V> Clone for IntTrie<V> {
+    fn clone(&self) -> Self {
+        match self {
+            IntTrie::Empty => IntTrie::Empty,
+            IntTrie::Leaf { key, value } => IntTrie::Leaf {
+                key: *key,
+                value: value.clone(),
+            },
+            IntTrie::Branch {
+                prefix,
+                mask,
+                left,
+                right,
+                size,
+            } => IntTrie::Branch {
+                prefix: *prefix,
+                mask: *mask,
+                left: left.clone(),
+                right: right.clone(),
+                size: *size,
+            },
+        }
+    }
+}
+
+// Bits of the key above the mask bit
+fn prefix_of(key: u64, mask: u64) -> u64 {
+    key & !(mask | (mask - 1))
+}
+
+fn matches_prefix(key: u64, prefix: u64, mask: u64) -> bool {
+    prefix_of(key, mask) == prefix
+}
+
+fn goes_left(key: u64, mask: u64) -> bool {
+    key & mask == 0
+}
+
+// Highest bit where the two differ
+fn branching_bit(a: u64, b: u64) -> u64 {
+    1 << (63 - (a ^ b).leading_zeros())
+}
+
+impl<V> IntTrie<V> {
+    pub fn empty() -> IntTrie<V> {
+        IntTrie::Empty
+    }
+    pub fn len(&self) -> usize {
+        match self {
+            IntTrie::Empty => 0,
+            IntTrie::Leaf { .. } => 1,
+            IntTrie::Branch { size, .. } => *size,
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        matches!(self, IntTrie::Empty)
+    }
+    pub fn find(&self, target_key: u64) -> Option<&V> {
+        let mut node = self;
+        loop {
+            match node {
+                IntTrie::Empty => return None,
+                IntTrie::Leaf { key, value } => {
+                    return if *key == target_key {
+                        Some(value.as_ref())
+                    } else {
+                        None
+                    }
+                }
+                IntTrie::Branch {
+                    prefix,
+                    mask,
+                    left,
+                    right,
+                    ..
+                } => {
+                    if !matches_prefix(target_key, *prefix, *mask) {
+                        return None;
+                    }
+                    node = if goes_left(target_key, *mask) {
+                        left
+                    } else {
+                        right
+                    };
+                }
+            }
+        }
+    }
+    pub fn contains_key(&self, key: u64) -> bool {
+        self.find(key).is_some()
+    }
+    pub fn put(&self, key: u64, value: V) -> IntTrie<V> {
+        self.upsert(key, |_| RefCounter::new(value))
+    }
+    pub fn delete(&self, target_key: u64) -> IntTrie<V> {
+        match self {
+            IntTrie::Empty => IntTrie::Empty,
+            IntTrie::Leaf { key, .. } => {
+                if *key == target_key {
+                    IntTrie::Empty
+                } else {
+                    self.clone()
+                }
+            }
+            IntTrie::Branch {
+                prefix,
+                mask,
+                left,
+                right,
+                ..
+            } => {
+                if !matches_prefix(target_key, *prefix, *mask) {
+                    return self.clone();
+                }
+                let (changed, kept) = if goes_left(target_key, *mask) {
+                    (left, right)
+                } else {
+                    (right, left)
+                };
+                let smaller = changed.delete(target_key);
+                if smaller.len() == changed.len() {
+                    return self.clone();
+                }
+                if smaller.is_empty() {
+                    return kept.as_ref().clone();
+                }
+                let smaller = RefCounter::new(smaller);
+                if goes_left(target_key, *mask) {
+                    IntTrie::branch(*prefix, *mask, smaller, kept.clone())
+                } else {
+                    IntTrie::branch(*prefix, *mask, kept.clone(), smaller)
+                }
+            }
+        }
+    }
+    // Keys from both sides. Keys found in both get merge(self value, other
+    // value), and subtrees found on one side only are shared as they are
+    pub fn union<F: Fn(&V, &V) -> V>(&self, other: &IntTrie<V>, merge: F) -> IntTrie<V> {
+        self.union_with(other, &merge)
+    }
+    fn union_with<F: Fn(&V, &V) -> V>(&self, other: &IntTrie<V>, merge: &F) -> IntTrie<V> {
+        match (self, other) {
+            (_, IntTrie::Empty) => self.clone(),
+            (IntTrie::Empty, _) => other.clone(),
+            (IntTrie::Leaf { key, value }, _) => other.upsert(*key, |existing| match existing {
+                Some(existing) => RefCounter::new(merge(value, existing)),
+                None => value.clone(),
+            }),
+            (_, IntTrie::Leaf { key, value }) => self.upsert(*key, |existing| match existing {
+                Some(existing) => RefCounter::new(merge(existing, value)),
+                None => value.clone(),
+            }),
+            (
+                IntTrie::Branch {
+                    prefix: p1,
+                    mask: m1,
+                    left: l1,
+                    right: r1,
+                    ..
+                },
+                IntTrie::Branch {
+                    prefix: p2,
+                    mask: m2,
+                    left: l2,
+                    right: r2,
+                    ..
+                },
+            ) => {
+                if m1 == m2 && p1 == p2 {
+                    IntTrie::branch(
+                        *p1,
+                        *m1,
+                        RefCounter::new(l1.union_with(l2, merge)),
+                        RefCounter::new(r1.union_with(r2, merge)),
+                    )
+                } else if m1 > m2 && matches_prefix(*p2, *p1, *m1) {
+                    // other fits entirely inside one side of self
+                    if goes_left(*p2, *m1) {
+                        IntTrie::branch(
+                            *p1,
+                            *m1,
+                            RefCounter::new(l1.union_with(other, merge)),
+                            r1.clone(),
+                        )
+                    } else {
+                        IntTrie::branch(
+                            *p1,
+                            *m1,
+                            l1.clone(),
+                            RefCounter::new(r1.union_with(other, merge)),
+                        )
+                    }
+                } else if m2 > m1 && matches_prefix(*p1, *p2, *m2) {
+                    if goes_left(*p1, *m2) {
+                        IntTrie::branch(
+                            *p2,
+                            *m2,
+                            RefCounter::new(self.union_with(l2, merge)),
+                            r2.clone(),
+                        )
+                    } else {
+                        IntTrie::branch(
+                            *p2,
+                            *m2,
+                            l2.clone(),
+                            RefCounter::new(self.union_with(r2, merge)),
+                        )
+                    }
+                } else {
+                    IntTrie::join(*p1, self.clone(), *p2, other.clone())
+                }
+            }
+        }
+    }
+    // Keys in increasing order
+    pub fn iter(&self) -> IntTrieIterator<'_, V> {
+        IntTrieIterator {
+            stack: vec![self],
+            remaining: self.len(),
+        }
+    }
+    pub fn keys(&self) -> impl Iterator<Item = u64> + '_ {
+        self.iter().map(|(key, _)| key)
+    }
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, value)| value)
+    }
+
+    fn branch(
+        prefix: u64,
+        mask: u64,
+        left: RefCounter<IntTrie<V>>,
+        right: RefCounter<IntTrie<V>>,
+    ) -> IntTrie<V> {
+        let size = left.len() + right.len();
+        IntTrie::Branch {
+            prefix,
+            mask,
+            left,
+            right,
+            size,
+        }
+    }
+    // Branch over two non-empty tries whose prefixes disagree
+    fn join(prefix: u64, trie: IntTrie<V>, other_prefix: u64, other: IntTrie<V>) -> IntTrie<V> {
+        let mask = branching_bit(prefix, other_prefix);
+        let (trie, other) = (RefCounter::new(trie), RefCounter::new(other));
+        if goes_left(prefix, mask) {
+            IntTrie::branch(prefix_of(prefix, mask), mask, trie, other)
+        } else {
+            IntTrie::branch(prefix_of(prefix, mask), mask, other, trie)
+        }
+    }
+    // Puts the value returned by f, which sees the value already at the key
+    fn upsert<F: FnOnce(Option<&RefCounter<V>>) -> RefCounter<V>>(
+        &self,
+        target_key: u64,
+        f: F,
+    ) -> IntTrie<V> {
+        match self {
+            IntTrie::Empty => IntTrie::Leaf {
+                key: target_key,
+                value: f(None),
+            },
+            IntTrie::Leaf { key, value } => {
+                if *key == target_key {
+                    IntTrie::Leaf {
+                        key: target_key,
+                        value: f(Some(value)),
+                    }
+                } else {
+                    let leaf = IntTrie::Leaf {
+                        key: target_key,
+                        value: f(None),
+                    };
+                    IntTrie::join(target_key, leaf, *key, self.clone())
+                }
+            }
+            IntTrie::Branch {
+                prefix,
+                mask,
+                left,
+                right,
+                ..
+            } => {
+                if !matches_prefix(target_key, *prefix, *mask) {
+                    let leaf = IntTrie::Leaf {
+                        key: target_key,
+                        value: f(None),
+                    };
+                    IntTrie::join(target_key, leaf, *prefix, self.clone())
+                } else if goes_left(target_key, *mask) {
+                    let left = RefCounter::new(left.upsert(target_key, f));
+                    IntTrie::branch(*prefix, *mask, left, right.clone())
+                } else {
+                    let right = RefCounter::new(right.upsert(target_key, f));
+                    IntTrie::branch(*prefix, *mask, left.clone(), right)
+                }
+            }
+        }
+    }
+}
+
+impl IntTrie<()> {
+    pub fn insert(&self, key: u64) -> Self {
+        self.put(key, ())
+    }
+    pub fn search(&self, key: u64) -> bool {
+        self.contains_key(key)
+    }
+}
+
+impl<V> FromIterator<(u64, V)> for IntTrie<V> {
+    fn from_iter<I: IntoIterator<Item = (u64, V)>>(iter: I) -> Self {
+        iter.into_iter()
+            .fold(IntTrie::empty(), |trie, (key, value)| trie.put(key, value))
+    }
+}
+
+pub struct IntTrieIterator<'a, V> {
+    stack: Vec<&'a IntTrie<V>>,
+    remaining: usize,
+}
+
+impl<'a, V> Iterator for IntTrieIterator<'a, V> {
+    type Item = (u64, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.stack.pop()? {
+                IntTrie::Empty => {}
+                IntTrie::Leaf { key, value } => {
+                    self.remaining -= 1;
+                    return Some((*key, value.as_ref()));
+                }
+                IntTrie::Branch { left, right, .. } => {
+                    self.stack.push(right);
+                    self.stack.push(left);
+                }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, V> ExactSizeIterator for IntTrieIterator<'a, V> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_int_trie_put_find_delete() {
+        let keys = [5, 0, u64::MAX, 1 << 63, 6, 4, 1 << 32];
+        let t: IntTrie<u64> = keys.iter().map(|&key| (key, key / 2)).collect();
+        assert_eq!(t.len(), keys.len());
+        for key in keys {
+            assert_eq!(t.find(key), Some(&(key / 2)));
+        }
+        assert!(t.find(7).is_none());
+        let mut sorted = keys.to_vec();
+        sorted.sort();
+        assert!(t.keys().eq(sorted.iter().copied()));
+        assert_eq!(t.iter().len(), keys.len());
+
+        let replaced = t.put(6, 100);
+        assert_eq!(replaced.find(6), Some(&100));
+        assert_eq!(replaced.len(), keys.len());
+        assert_eq!(t.find(6), Some(&3));
+
+        let removed = t.delete(1 << 63).delete(5).delete(12345);
+        assert_eq!(removed.len(), keys.len() - 2);
+        assert!(!removed.contains_key(1 << 63));
+        assert!(removed.contains_key(u64::MAX));
+        assert!(t.contains_key(5));
+        let emptied = keys.iter().fold(t.clone(), |t, &key| t.delete(key));
+        assert!(emptied.is_empty());
+    }
+
+    #[test]
+    fn test_int_trie_union() {
+        let evens: IntTrie<&str> = (0..100).step_by(2).map(|key| (key, "even")).collect();
+        let threes: IntTrie<&str> = (0..100).step_by(3).map(|key| (key, "three")).collect();
+        let both = evens.union(&threes, |_, _| "both");
+        assert_eq!(both.len(), 50 + 34 - 17);
+        assert_eq!(both.find(6), Some(&"both"));
+        assert_eq!(both.find(4), Some(&"even"));
+        assert_eq!(both.find(9), Some(&"three"));
+        assert!(both.find(7).is_none());
+        assert!(both.keys().zip(both.keys().skip(1)).all(|(a, b)| a < b));
+
+        let far: IntTrie<()> = IntTrie::empty().insert(u64::MAX).insert(1 << 40);
+        let near: IntTrie<()> = IntTrie::empty().insert(1).insert(2);
+        let all = near.union(&far, |_, _| ());
+        assert!(all.keys().eq([1, 2, 1 << 40, u64::MAX]));
+        assert!(all.search(u64::MAX) && !all.search(3));
+    }
+}
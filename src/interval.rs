@@ -0,0 +1,249 @@
+use std::{cmp::Ordering, ops::Range};
+
+use crate::{
+    avl::{Compare, Summary, AVL},
+    RefCounter,
+};
+
+// Orders intervals by start, then by end
+struct ByStartEnd;
+
+impl<K: Ord> Compare<Range<K>> for ByStartEnd {
+    fn compare(a: &Range<K>, b: &Range<K>) -> Ordering {
+        a.start.cmp(&b.start).then_with(|| a.end.cmp(&b.end))
+    }
+}
+
+// The interval with the largest end in a subtree
+struct MaxEnd<K>(RefCounter<Range<K>>);
+
+impl<K> Clone for MaxEnd<K> {
+    fn clone(&self) -> Self {
+        MaxEnd(self.0.clone())
+    }
+}
+
+impl<K: Ord, V> Summary<Range<K>, V> for MaxEnd<K> {
+    fn summarize(
+        key: &RefCounter<Range<K>>,
+        _: &RefCounter<V>,
+        left: Option<&Self>,
+        right: Option<&Self>,
+    ) -> Self {
+        let mut max_end = key;
+        for child in [left, right].into_iter().flatten() {
+            if child.0.end > max_end.end {
+                max_end = &child.0;
+            }
+        }
+        MaxEnd(max_end.clone())
+    }
+}
+
+type Tree<K, V> = AVL<Range<K>, V, ByStartEnd, MaxEnd<K>>;
+
+// Half-open intervals [start, end) mapped to values
+pub struct IntervalMap<K, V> {
+    root: Tree<K, V>,
+}
+
+impl<K, V> Clone for IntervalMap<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root.clone(),
+        }
+    }
+}
+
+impl<K: Ord, V> IntervalMap<K, V> {
+    pub fn empty() -> Self {
+        Self { root: AVL::Empty }
+    }
+    pub fn len(&self) -> usize {
+        self.root.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.root.is_empty()
+    }
+    pub fn insert(&self, interval: Range<K>, value: V) -> Self {
+        Self {
+            root: self.root.put(interval, value),
+        }
+    }
+    pub fn remove(&self, interval: &Range<K>) -> Self {
+        Self {
+            root: self.root.delete(interval),
+        }
+    }
+    pub fn get(&self, target: &Range<K>) -> Option<&V> {
+        self.root.find(target)
+    }
+    pub fn iter(&self) -> IntervalIterator<'_, K, V> {
+        IntervalIterator::new(&self.root, Query::All)
+    }
+    // Intervals containing the point
+    pub fn stab<'a>(&'a self, point: &'a K) -> IntervalIterator<'a, K, V> {
+        IntervalIterator::new(&self.root, Query::Point(point))
+    }
+    // Intervals sharing at least one point with the query
+    pub fn overlapping<'a>(&'a self, query: &'a Range<K>) -> IntervalIterator<'a, K, V> {
+        IntervalIterator::new(&self.root, Query::Overlap(query))
+    }
+}
+
+enum Query<'a, K> {
+    All,
+    Point(&'a K),
+    Overlap(&'a Range<K>),
+}
+
+impl<'a, K: Ord> Query<'a, K> {
+    fn matches(&self, interval: &Range<K>) -> bool {
+        match self {
+            Query::All => true,
+            Query::Point(point) => interval.start <= **point && **point < interval.end,
+            Query::Overlap(query) => interval.start < query.end && query.start < interval.end,
+        }
+    }
+    // No interval in a subtree whose largest end is this can match
+    fn excludes_max_end(&self, max_end: &K) -> bool {
+        match self {
+            Query::All => false,
+            Query::Point(point) => max_end <= *point,
+            Query::Overlap(query) => *max_end <= query.start,
+        }
+    }
+    // Nothing starting at or after this can match
+    fn excludes_start(&self, start: &K) -> bool {
+        match self {
+            Query::All => false,
+            Query::Point(point) => start > *point,
+            Query::Overlap(query) => *start >= query.end,
+        }
+    }
+}
+
+enum Pending<'a, K, V> {
+    Tree(&'a Tree<K, V>),
+    Entry(&'a Range<K>, &'a V),
+}
+
+// In-order walk that skips the subtrees the query rules out
+pub struct IntervalIterator<'a, K, V> {
+    query: Query<'a, K>,
+    stack: Vec<Pending<'a, K, V>>,
+}
+
+impl<'a, K: Ord, V> IntervalIterator<'a, K, V> {
+    fn new(root: &'a Tree<K, V>, query: Query<'a, K>) -> Self {
+        Self {
+            query,
+            stack: vec![Pending::Tree(root)],
+        }
+    }
+}
+
+impl<'a, K: Ord, V> Iterator for IntervalIterator<'a, K, V> {
+    type Item = (&'a Range<K>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(pending) = self.stack.pop() {
+            match pending {
+                Pending::Entry(interval, value) => {
+                    if self.query.matches(interval) {
+                        return Some((interval, value));
+                    }
+                }
+                Pending::Tree(AVL::Empty) => {}
+                Pending::Tree(AVL::Node {
+                    key: interval,
+                    value,
+                    left,
+                    right,
+                    summary: MaxEnd(max_end),
+                    ..
+                }) => {
+                    if self.query.excludes_max_end(&max_end.end) {
+                        continue;
+                    }
+                    if !self.query.excludes_start(&interval.start) {
+                        self.stack.push(Pending::Tree(right));
+                        self.stack.push(Pending::Entry(interval, value));
+                    }
+                    self.stack.push(Pending::Tree(left));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bookings() -> IntervalMap<i32, &'static str> {
+        IntervalMap::empty()
+            .insert(9..12, "standup")
+            .insert(10..11, "review")
+            .insert(13..15, "lunch")
+            .insert(14..18, "workshop")
+            .insert(1..2, "night")
+    }
+
+    #[test]
+    fn test_interval_map_basics() {
+        let m = bookings();
+        assert_eq!(m.len(), 5);
+        assert_eq!(m.get(&(13..15)), Some(&"lunch"));
+        assert!(m.get(&(13..14)).is_none());
+        let replaced = m.insert(13..15, "brunch");
+        assert_eq!(replaced.len(), 5);
+        assert_eq!(replaced.get(&(13..15)), Some(&"brunch"));
+        let removed = m.remove(&(9..12));
+        assert_eq!(removed.len(), 4);
+        assert!(removed.get(&(9..12)).is_none());
+        assert_eq!(m.remove(&(0..1)).len(), 5);
+        let starts: Vec<_> = m.iter().map(|(r, _)| r.start).collect();
+        assert_eq!(starts, vec![1, 9, 10, 13, 14]);
+    }
+
+    #[test]
+    fn test_interval_map_queries() {
+        let m = bookings();
+        let at = |p: i32| -> Vec<&str> { m.stab(&p).map(|(_, v)| *v).collect() };
+        assert_eq!(at(10), vec!["standup", "review"]);
+        assert_eq!(at(12), Vec::<&str>::new());
+        assert_eq!(at(14), vec!["lunch", "workshop"]);
+        assert_eq!(at(15), vec!["workshop"]);
+
+        let query = 11..14;
+        let found: Vec<_> = m.overlapping(&query).map(|(_, v)| *v).collect();
+        assert_eq!(found, vec!["standup", "lunch"]);
+        let query = 18..20;
+        assert_eq!(m.overlapping(&query).count(), 0);
+    }
+
+    #[test]
+    fn test_interval_map_matches_linear_scan() {
+        let intervals: Vec<Range<i32>> = (0..200)
+            .map(|i| (i * 7) % 101..(i * 7) % 101 + i % 13 + 1)
+            .collect();
+        let m = intervals
+            .iter()
+            .fold(IntervalMap::empty(), |m, r| m.insert(r.clone(), ()));
+        for point in 0..120 {
+            let expected = m.iter().filter(|(r, _)| r.contains(&point)).count();
+            assert_eq!(m.stab(&point).count(), expected);
+        }
+        let m = intervals.iter().step_by(3).fold(m, |m, r| m.remove(r));
+        for start in 0..110 {
+            let query = start..start + 5;
+            let expected = m
+                .iter()
+                .filter(|(r, _)| r.start < query.end && query.start < r.end)
+                .count();
+            assert_eq!(m.overlapping(&query).count(), expected);
+        }
+    }
+}
@@ -1,4 +1,7 @@
+pub mod aho_corasick;
 pub mod avl;
+pub mod bit_trie;
+pub mod byte_trie;
 #[cfg(feature = "thread_safe")]
 pub type RefCounter<T> = std::sync::Arc<T>;
 
@@ -6,6 +9,21 @@ pub type RefCounter<T> = std::sync::Arc<T>;
 pub type RefCounter<T> = std::rc::Rc<T>;
 
 pub mod deque;
+pub mod frozen;
+pub mod hashed_trie;
 pub mod hashmap;
+pub mod int_trie;
+pub mod interval;
 pub mod list;
+pub mod multimap;
+pub mod ordered_set;
+#[cfg(feature = "serde")]
+mod serde_impl;
+#[cfg(feature = "proptest")]
+pub mod strategy;
+pub mod string_trie;
+pub mod treap;
 pub mod trie;
+pub mod trie_map;
+pub mod versioned;
+pub mod weight_balanced;
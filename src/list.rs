@@ -1,5 +1,26 @@
+use std::{
+    cmp::Ordering,
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    mem,
+};
+
 use crate::RefCounter;
 
+// Builds a List in the order the values are written, like vec!
+#[macro_export]
+macro_rules! list {
+    () => {
+        $crate::list::List::empty()
+    };
+    ($value:expr; $n:expr) => {
+        $crate::list::List::from(vec![$value; $n])
+    };
+    ($($value:expr),+ $(,)?) => {
+        $crate::list::List::from(vec![$($value),+])
+    };
+}
+
 enum ListNode<T> {
     Empty,
     Value {
@@ -20,17 +41,88 @@ impl<T> Clone for ListNode<T> {
     }
 }
 
+// Walks the rest of the spine in a loop, so dropping a long list cannot
+// overflow the call stack. It stops at the first node still shared with
+// another list
+impl<T> Drop for ListNode<T> {
+    fn drop(&mut self) {
+        let ListNode::Value { next_node, .. } = self else {
+            return;
+        };
+        // The node after next is held here while next is freed, so freeing
+        // next stops right there
+        while let Option::Some(ListNode::Value {
+            next_node: after, ..
+        }) = RefCounter::get_mut(next_node)
+        {
+            *next_node = after.clone();
+        }
+    }
+}
+
 impl<T> Clone for List<T> {
     fn clone(&self) -> Self {
         List {
             head: self.head.clone(),
             len: self.len,
+            last: self.last.clone(),
+        }
+    }
+}
+
+impl<T: Debug> Debug for List<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter_ref()).finish()
+    }
+}
+
+impl<T: PartialEq> PartialEq for List<T> {
+    fn eq(&self, other: &Self) -> bool {
+        if self.len != other.len {
+            return false;
+        }
+        let (mut left, mut right) = (&self.head, &other.head);
+        loop {
+            // The rest of both lists is the very same nodes
+            if RefCounter::ptr_eq(left, right) {
+                return true;
+            }
+            match (left.as_ref(), right.as_ref()) {
+                (
+                    ListNode::Value {
+                        value: v1,
+                        next_node: n1,
+                    },
+                    ListNode::Value {
+                        value: v2,
+                        next_node: n2,
+                    },
+                ) => {
+                    if !(RefCounter::ptr_eq(v1, v2) || v1 == v2) {
+                        return false;
+                    }
+                    (left, right) = (n1, n2);
+                }
+                _ => return true,
+            }
+        }
+    }
+}
+
+impl<T: Eq> Eq for List<T> {}
+
+impl<T: Hash> Hash for List<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len);
+        for value in self.iter_ref() {
+            value.hash(state);
         }
     }
 }
 
 pub struct ListIterator<T> {
     current: RefCounter<ListNode<T>>,
+    remaining: usize,
 }
 
 impl<T> Iterator for ListIterator<T> {
@@ -42,40 +134,225 @@ impl<T> Iterator for ListIterator<T> {
             ListNode::Value { value, next_node } => {
                 let return_value = value.clone();
                 self.current = next_node.clone();
+                self.remaining -= 1;
                 Some(return_value)
             }
         }
     }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for ListIterator<T> {}
+
+pub struct ListRefIterator<'a, T> {
+    current: &'a ListNode<T>,
+    remaining: usize,
+}
+
+impl<'a, T> Iterator for ListRefIterator<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.current {
+            ListNode::Empty => None,
+            ListNode::Value { value, next_node } => {
+                self.current = next_node.as_ref();
+                self.remaining -= 1;
+                Some(value.as_ref())
+            }
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for ListRefIterator<'_, T> {}
+
+pub struct ListRevIterator<'a, T> {
+    stack: Vec<&'a T>,
+}
+
+impl<'a, T> Iterator for ListRevIterator<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.stack.pop()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.stack.len(), Some(self.stack.len()))
+    }
+}
+
+impl<T> ExactSizeIterator for ListRevIterator<'_, T> {}
+
+impl<'a, T> IntoIterator for &'a List<T> {
+    type Item = &'a T;
+    type IntoIter = ListRefIterator<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_ref()
+    }
+}
+
+// Values only owned by the list are moved out instead of cloned
+pub struct ListIntoIterator<T> {
+    current: Option<RefCounter<ListNode<T>>>,
+    remaining: usize,
+}
+
+impl<T: Clone> Iterator for ListIntoIterator<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.current.take()?;
+        let (value, next_node) = match node.as_ref() {
+            ListNode::Empty => return None,
+            ListNode::Value { value, next_node } => (value.clone(), next_node.clone()),
+        };
+        // Frees the node when the list owned it, leaving value unique
+        drop(node);
+        self.current = Some(next_node);
+        self.remaining -= 1;
+        Some(RefCounter::try_unwrap(value).unwrap_or_else(|shared| T::clone(&shared)))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T: Clone> ExactSizeIterator for ListIntoIterator<T> {}
+
+impl<T: Clone> IntoIterator for List<T> {
+    type Item = T;
+    type IntoIter = ListIntoIterator<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        ListIntoIterator {
+            current: Some(self.head),
+            remaining: self.len,
+        }
+    }
 }
 
+// A list being rebuilt without some of its values. Values kept since the
+// last one left out may still end up shared as part of the original nodes
+struct Retained<T> {
+    front: Vec<RefCounter<T>>,
+    since_skipped: Vec<RefCounter<T>>,
+    shared: List<T>,
+}
+
+impl<T> Retained<T> {
+    fn new(list: &List<T>) -> Self {
+        Retained {
+            front: Vec::new(),
+            since_skipped: Vec::new(),
+            shared: list.clone(),
+        }
+    }
+    fn keep(&mut self, value: RefCounter<T>) {
+        self.since_skipped.push(value);
+    }
+    // Leaves out the value whose following nodes are rest
+    fn skip(&mut self, rest: &List<T>) {
+        self.front.append(&mut self.since_skipped);
+        self.shared = rest.clone();
+    }
+    fn finish(self) -> List<T> {
+        List::prepend_all(self.front, self.shared)
+    }
+}
+
+pub struct ListChunks<T> {
+    rest: List<T>,
+    size: usize,
+}
+
+impl<T> Iterator for ListChunks<T> {
+    type Item = List<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rest.is_empty() {
+            return None;
+        }
+        if self.rest.length() <= self.size {
+            return Some(mem::replace(&mut self.rest, List::empty()));
+        }
+        let (chunk, rest) = self.rest.split_at(self.size);
+        self.rest = rest;
+        Some(chunk)
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let count = self.rest.length().div_ceil(self.size);
+        (count, Some(count))
+    }
+}
+
+impl<T> ExactSizeIterator for ListChunks<T> {}
+
 pub struct List<T> {
     head: RefCounter<ListNode<T>>,
     len: usize,
+    // Value of the last node, so last() does not walk the list
+    last: Option<RefCounter<T>>,
 }
 
 impl<T> List<T> {
     pub fn iter(&self) -> ListIterator<T> {
         ListIterator {
             current: self.head.clone(),
+            remaining: self.len,
+        }
+    }
+    // Borrows the values in place, with no reference count updates
+    pub fn iter_ref(&self) -> ListRefIterator<'_, T> {
+        ListRefIterator {
+            current: self.head.as_ref(),
+            remaining: self.len,
+        }
+    }
+    // Back to front. Collects a reference to every value up front, rather
+    // than building a reversed list
+    pub fn iter_rev(&self) -> ListRevIterator<'_, T> {
+        ListRevIterator {
+            stack: self.iter_ref().collect(),
         }
     }
     pub fn split(&self) -> (List<T>, List<T>) {
-        let mut first = List::<T>::empty();
-        let mut second = List::<T>::empty();
-        let mut current = self.clone();
-        let half = self.length() / 2;
-        let other_half = self.length() - half;
-        for _ in 0..half {
-            let (value_rc, new_list) = current.pop_front_rc().unwrap();
-            first = first.push_front_rc(value_rc);
-            current = new_list;
-        }
-        for _ in 0..other_half {
-            let (value_rc, new_list) = current.pop_front_rc().unwrap();
-            second = second.push_front_rc(value_rc);
-            current = new_list;
-        }
-        (first.reverse(), second.reverse())
+        self.split_at(self.length() / 2)
+    }
+    // The first n values, or all of them when the list is shorter
+    pub fn take(&self, n: usize) -> List<T> {
+        self.split_at(n).0
+    }
+    // The nodes after the first n, shared with self
+    pub fn drop(&self, n: usize) -> List<T> {
+        let n = n.min(self.len);
+        let mut node = &self.head;
+        for _ in 0..n {
+            if let ListNode::Value { next_node, .. } = node.as_ref() {
+                node = next_node;
+            }
+        }
+        self.suffix(node.clone(), self.len - n)
+    }
+    // Sub-lists of size values, the last one possibly shorter. Values are
+    // shared, and so are the nodes of the last chunk, which is a suffix of
+    // self. The others end earlier than self does, so they get new nodes
+    pub fn chunks(&self, size: usize) -> ListChunks<T> {
+        assert!(size > 0, "chunk size must be non-zero");
+        ListChunks {
+            rest: self.clone(),
+            size,
+        }
+    }
+    // Same as (take(n), drop(n)) in a single walk
+    pub fn split_at(&self, n: usize) -> (List<T>, List<T>) {
+        let (front, rest) = self.cut(n.min(self.len)).unwrap();
+        (List::prepend_all(front, List::empty()), rest)
     }
     pub fn reverse(&self) -> List<T> {
         let mut node = self.head.clone();
@@ -91,16 +368,27 @@ impl<T> List<T> {
         List {
             head: last_node,
             len: self.len,
+            last: self.iter().next(),
         }
     }
     pub fn empty() -> List<T> {
         return List {
             head: RefCounter::new(ListNode::Empty),
             len: 0,
+            last: Option::None,
         };
     }
-    fn push_front_rc(&self, rc_value: RefCounter<T>) -> List<T> {
+    // A list made of the last len nodes of self, starting at head
+    fn suffix(&self, head: RefCounter<ListNode<T>>, len: usize) -> List<T> {
         List {
+            head,
+            len,
+            last: self.last.clone().filter(|_| len > 0),
+        }
+    }
+    pub(crate) fn push_front_rc(&self, rc_value: RefCounter<T>) -> List<T> {
+        List {
+            last: self.last.clone().or_else(|| Option::Some(rc_value.clone())),
             head: RefCounter::new(ListNode::Value {
                 value: rc_value,
                 next_node: self.head.clone(),
@@ -117,38 +405,214 @@ impl<T> List<T> {
     pub fn length(&self) -> usize {
         self.len
     }
+    // Same as uncons, under the name that pairs it with pop_front
     pub fn pop_front_rc(&self) -> Option<(RefCounter<T>, List<T>)> {
+        self.uncons()
+    }
+    pub fn pop_front(&self) -> Option<(&T, List<T>)> {
         match self.head.as_ref() {
             ListNode::Empty => Option::None,
             ListNode::Value {
                 value,
                 ref next_node,
-            } => Option::Some((
-                value.clone(),
-                List {
-                    head: next_node.clone(),
-                    len: self.len - 1,
-                },
-            )),
+            } => Option::Some((value, self.suffix(next_node.clone(), self.len - 1))),
         }
     }
-    pub fn pop_front(&self) -> Option<(&T, List<T>)> {
+    pub fn front(&self) -> Option<&T> {
+        self.pop_front().map(|(e, _)| e)
+    }
+    pub fn head(&self) -> Option<&T> {
+        self.front()
+    }
+    // Every node but the first, shared with self
+    pub fn tail(&self) -> Option<List<T>> {
+        self.uncons().map(|(_, rest)| rest)
+    }
+    // The first value and the rest, both owned, for recursing over the list
+    pub fn uncons(&self) -> Option<(RefCounter<T>, List<T>)> {
         match self.head.as_ref() {
             ListNode::Empty => Option::None,
             ListNode::Value {
                 value,
                 ref next_node,
-            } => Option::Some((
-                value,
-                List {
-                    head: next_node.clone(),
-                    len: self.len - 1,
-                },
-            )),
+            } => Option::Some((value.clone(), self.suffix(next_node.clone(), self.len - 1))),
         }
     }
-    pub fn front(&self) -> Option<&T> {
-        self.pop_front().map(|(e, _)| e)
+    pub fn last(&self) -> Option<&T> {
+        self.last.as_deref()
+    }
+    pub fn position<F: FnMut(&T) -> bool>(&self, predicate: F) -> Option<usize> {
+        self.iter_ref().position(predicate)
+    }
+    // Walks the first index nodes
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.iter_ref().nth(index)
+    }
+    // Only the nodes of self are copied, other becomes the tail as is
+    pub fn append(&self, other: &List<T>) -> List<T> {
+        List::prepend_all(self.iter().collect(), other.clone())
+    }
+    // The values of the first index nodes, and the list after them
+    fn cut(&self, index: usize) -> Option<(Vec<RefCounter<T>>, List<T>)> {
+        let mut front = Vec::with_capacity(index);
+        let mut rest = self.clone();
+        for _ in 0..index {
+            let (value, next) = rest.pop_front_rc()?;
+            front.push(value);
+            rest = next;
+        }
+        Option::Some((front, rest))
+    }
+    fn prepend_all(front: Vec<RefCounter<T>>, rest: List<T>) -> List<T> {
+        front
+            .into_iter()
+            .rev()
+            .fold(rest, |list, value| list.push_front_rc(value))
+    }
+    pub fn map<U, F: FnMut(&T) -> U>(&self, f: F) -> List<U> {
+        let mapped: Vec<U> = self.iter_ref().map(f).collect();
+        List::from(mapped)
+    }
+    // Stable bottom-up merge sort. Values are moved between runs by their
+    // RefCounter, so none of them is cloned
+    pub fn sort_by<F: FnMut(&T, &T) -> Ordering>(&self, mut compare: F) -> List<T> {
+        let mut runs: Vec<Vec<RefCounter<T>>> = self.iter().map(|value| vec![value]).collect();
+        while runs.len() > 1 {
+            let mut merged = Vec::with_capacity(runs.len().div_ceil(2));
+            let mut pairs = runs.into_iter();
+            while let Option::Some(left) = pairs.next() {
+                match pairs.next() {
+                    Option::Some(right) => merged.push(merge_runs(left, right, &mut compare)),
+                    Option::None => merged.push(left),
+                }
+            }
+            runs = merged;
+        }
+        List::prepend_all(runs.pop().unwrap_or_default(), List::empty())
+    }
+    // Pairs up values by position, as long as the shorter list. The values
+    // themselves are shared with both lists
+    pub fn zip<U>(&self, other: &List<U>) -> List<(RefCounter<T>, RefCounter<U>)> {
+        self.iter().zip(other.iter()).collect()
+    }
+    // Everything after the last value that is left out is shared
+    pub fn filter<F: FnMut(&T) -> bool>(&self, mut keep: F) -> List<T> {
+        self.retain_rc(|value| keep(value))
+    }
+    // Drops values for which same(previous, value) holds, where previous is
+    // the value right before. Shares the suffix like filter
+    pub fn dedup_by<F: FnMut(&T, &T) -> bool>(&self, mut same: F) -> List<T> {
+        let mut previous: Option<RefCounter<T>> = Option::None;
+        self.retain_rc(|value| {
+            let repeated = previous.as_ref().is_some_and(|p| same(p, value));
+            previous = Option::Some(value.clone());
+            !repeated
+        })
+    }
+    fn retain_rc<F: FnMut(&RefCounter<T>) -> bool>(&self, mut keep: F) -> List<T> {
+        let mut kept = Retained::new(self);
+        let mut rest = self.clone();
+        while let Option::Some((value, next)) = rest.pop_front_rc() {
+            if keep(&value) {
+                kept.keep(value);
+            } else {
+                kept.skip(&next);
+            }
+            rest = next;
+        }
+        kept.finish()
+    }
+    // Values matching the predicate and the others, in one walk. Each side
+    // shares the nodes after the last value that went to the other side
+    pub fn partition<F: FnMut(&T) -> bool>(&self, mut predicate: F) -> (List<T>, List<T>) {
+        let mut matching = Retained::new(self);
+        let mut others = Retained::new(self);
+        let mut rest = self.clone();
+        while let Option::Some((value, next)) = rest.pop_front_rc() {
+            if predicate(&value) {
+                matching.keep(value);
+                others.skip(&next);
+            } else {
+                others.keep(value);
+                matching.skip(&next);
+            }
+            rest = next;
+        }
+        (matching.finish(), others.finish())
+    }
+    // None when index is past the end. Nodes after index are shared
+    pub fn insert_at(&self, index: usize, value: T) -> Option<List<T>> {
+        let (front, rest) = self.cut(index)?;
+        Option::Some(List::prepend_all(front, rest.push_front(value)))
+    }
+    pub fn remove_at(&self, index: usize) -> Option<List<T>> {
+        let (front, rest) = self.cut(index)?;
+        let (_, rest) = rest.pop_front_rc()?;
+        Option::Some(List::prepend_all(front, rest))
+    }
+}
+
+impl<T: PartialEq> List<T> {
+    pub fn dedup(&self) -> List<T> {
+        self.dedup_by(|a, b| a == b)
+    }
+}
+
+impl<T: Ord> List<T> {
+    pub fn sort(&self) -> List<T> {
+        self.sort_by(T::cmp)
+    }
+}
+
+// Takes from left on ties, which keeps the sort stable
+fn merge_runs<T, F: FnMut(&T, &T) -> Ordering>(
+    left: Vec<RefCounter<T>>,
+    right: Vec<RefCounter<T>>,
+    compare: &mut F,
+) -> Vec<RefCounter<T>> {
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+    while let (Option::Some(l), Option::Some(r)) = (left.peek(), right.peek()) {
+        let next = match compare(l, r) {
+            Ordering::Greater => right.next(),
+            _ => left.next(),
+        };
+        merged.extend(next);
+    }
+    merged.extend(left);
+    merged.extend(right);
+    merged
+}
+
+impl<T: PartialEq> List<T> {
+    pub fn contains(&self, value: &T) -> bool {
+        self.iter_ref().any(|v| v == value)
+    }
+}
+
+impl<T: Clone> List<T> {
+    pub fn from_slice(values: &[T]) -> List<T> {
+        values
+            .iter()
+            .rev()
+            .fold(List::empty(), |list, value| list.push_front(value.clone()))
+    }
+}
+
+// Pushed back to front, so the list keeps the order of the vector
+impl<T> From<Vec<T>> for List<T> {
+    fn from(values: Vec<T>) -> Self {
+        values
+            .into_iter()
+            .rev()
+            .fold(List::empty(), |list, value| list.push_front(value))
+    }
+}
+
+impl<T> FromIterator<T> for List<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        List::from(iter.into_iter().collect::<Vec<T>>())
     }
 }
 
@@ -169,6 +633,135 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_iter_ref() {
+        let l = List::from(vec!["a".to_string(), "b".to_string()]);
+        let values: Vec<&String> = l.iter_ref().collect();
+        assert_eq!(values, ["a", "b"]);
+        assert!(std::ptr::eq(values[0], l.front().unwrap()));
+        assert!(List::<u8>::empty().iter_ref().next().is_none());
+    }
+
+    #[test]
+    fn test_iter_len() {
+        let l: List<i32> = (0..5).collect();
+        let mut iter = l.iter();
+        assert_eq!(iter.len(), 5);
+        iter.next();
+        assert_eq!(iter.size_hint(), (4, Some(4)));
+        let back = l.drop(3);
+        let mut refs = back.iter_ref();
+        assert_eq!(refs.len(), 2);
+        refs.by_ref().count();
+        assert_eq!(refs.len(), 0);
+    }
+
+    #[test]
+    fn test_into_iter() {
+        let l = List::from(vec![1, 2, 3]);
+        let mut sum = 0;
+        for value in &l {
+            sum += value;
+        }
+        assert_eq!(sum, 6);
+
+        let shared = l.drop(1);
+        let owned: Vec<i32> = l.into_iter().collect();
+        assert_eq!(owned, [1, 2, 3]);
+        assert_eq!(shared.into_iter().len(), 2);
+
+        let unique = List::from(vec![String::from("a"), String::from("b")]);
+        let first_ptr = unique.front().unwrap().as_ptr();
+        let strings: Vec<String> = unique.into_iter().collect();
+        assert_eq!(strings[0].as_ptr(), first_ptr);
+    }
+
+    #[test]
+    fn test_list_drop_long() {
+        let long: List<usize> = (0..1_000_000).collect();
+        let shared = long.drop(10);
+        drop(long);
+        assert_eq!(shared.length(), 999_990);
+        assert_eq!(shared.front(), Some(&10));
+        assert_eq!(shared.last(), Some(&999_999));
+        let mut iter = shared.iter();
+        iter.next();
+        drop(shared);
+        assert_eq!(iter.next().as_deref(), Some(&11));
+    }
+
+    #[test]
+    fn test_list_macro() {
+        let l = list![1, 2, 3];
+        assert_eq!(l, List::from(vec![1, 2, 3]));
+        assert_eq!(list!["a", "b",].length(), 2);
+        assert_eq!(list![0u8; 4], List::from(vec![0, 0, 0, 0]));
+        let empty: List<i32> = list![];
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_list_head_and_tail() {
+        fn sum(list: &List<i32>) -> i32 {
+            match list.uncons() {
+                Some((value, rest)) => *value + sum(&rest),
+                None => 0,
+            }
+        }
+        let l = list![1, 2, 3];
+        assert_eq!(sum(&l), 6);
+        assert_eq!(l.head(), Some(&1));
+        let tail = l.tail().unwrap();
+        assert_eq!(tail, list![2, 3]);
+        assert!(RefCounter::ptr_eq(&tail.head, &l.drop(1).head));
+        let (first, _) = l.uncons().unwrap();
+        assert_eq!(tail.push_front_rc(first), l);
+        assert!(List::<i32>::empty().tail().is_none());
+        assert!(List::<i32>::empty().head().is_none());
+    }
+
+    #[test]
+    fn test_list_chunks() {
+        let queue: List<i32> = (1..=7).collect();
+        let batches: Vec<List<i32>> = queue.chunks(3).collect();
+        assert_eq!(batches, vec![list![1, 2, 3], list![4, 5, 6], list![7]]);
+        assert!(RefCounter::ptr_eq(&batches[2].head, &queue.drop(6).head));
+        assert_eq!(batches[1].last(), Some(&6));
+        assert_eq!(queue.chunks(3).len(), 3);
+        assert_eq!(queue.chunks(7).count(), 1);
+        assert_eq!(List::<i32>::empty().chunks(2).count(), 0);
+    }
+
+    #[test]
+    fn test_list_partition() {
+        let l: List<i32> = (1..=8).collect();
+        let (even, odd) = l.partition(|v| v % 2 == 0);
+        assert_eq!(even, list![2, 4, 6, 8]);
+        assert_eq!(odd, list![1, 3, 5, 7]);
+        assert_eq!((even.length(), odd.length()), (4, 4));
+        assert!(RefCounter::ptr_eq(&even.drop(3).head, &l.drop(7).head));
+
+        let (small, large) = l.partition(|v| *v <= 3);
+        assert_eq!(small, list![1, 2, 3]);
+        assert!(RefCounter::ptr_eq(&large.head, &l.drop(3).head));
+        let (all, none) = l.partition(|_| true);
+        assert!(RefCounter::ptr_eq(&all.head, &l.head));
+        assert!(none.is_empty());
+    }
+
+    #[test]
+    fn test_iter_rev() {
+        let log = list!["first", "second", "third"];
+        let newest_last: Vec<_> = log.iter_rev().copied().collect();
+        assert_eq!(newest_last, ["third", "second", "first"]);
+        assert_eq!(log.iter_rev().len(), 3);
+        assert!(std::ptr::eq(
+            log.iter_rev().last().unwrap(),
+            log.front().unwrap()
+        ));
+        assert!(List::<u8>::empty().iter_rev().next().is_none());
+    }
+
     #[test]
     fn test_split() {
         let l = List::empty()
@@ -203,6 +796,214 @@ mod tests {
         assert_eq!(remaining_list.front(), Some(&123));
     }
 
+    #[test]
+    fn test_list_from_iter() {
+        let list: List<i32> = (1..=4).collect();
+        assert_eq!(list.length(), 4);
+        let values: Vec<i32> = list.iter().map(|value| *value).collect();
+        assert_eq!(values, vec![1, 2, 3, 4]);
+        assert!(std::iter::empty::<i32>().collect::<List<_>>().is_empty());
+    }
+
+    #[test]
+    fn test_list_from_vec_and_slice() {
+        let from_vec = List::from(vec!['a', 'b', 'c']);
+        let from_slice = List::from_slice(&['a', 'b', 'c']);
+        for list in [&from_vec, &from_slice] {
+            let values: String = list.iter().map(|value| *value).collect();
+            assert_eq!(values, "abc");
+            assert_eq!(list.length(), 3);
+        }
+        assert!(List::<u8>::from_slice(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_list_append() {
+        let front = List::from(vec![1, 2]);
+        let back = List::from(vec![3, 4, 5]);
+        let joined = front.append(&back);
+        let values: Vec<i32> = joined.iter().map(|value| *value).collect();
+        assert_eq!(values, vec![1, 2, 3, 4, 5]);
+        assert_eq!(joined.length(), 5);
+        let (_, rest) = joined.pop_front().unwrap();
+        let (_, rest) = rest.pop_front().unwrap();
+        assert!(RefCounter::ptr_eq(&rest.head, &back.head));
+        assert_eq!(front.length(), 2);
+        assert_eq!(List::empty().append(&back).length(), 3);
+        assert_eq!(front.append(&List::empty()).length(), 2);
+    }
+
+    #[test]
+    fn test_list_get() {
+        let list = List::from(vec!["a", "b", "c"]);
+        assert_eq!(list.get(0), Some(&"a"));
+        assert_eq!(list.get(2), Some(&"c"));
+        assert!(list.get(3).is_none());
+        assert!(List::<u8>::empty().get(0).is_none());
+    }
+
+    #[test]
+    fn test_list_insert_and_remove_at() {
+        let lines = List::from(vec!["a", "b", "d"]);
+        let inserted = lines.insert_at(2, "c").unwrap();
+        assert_eq!(
+            inserted.iter().map(|v| *v).collect::<Vec<_>>(),
+            ["a", "b", "c", "d"]
+        );
+        assert_eq!(inserted.length(), 4);
+        let (_, tail) = lines.pop_front().unwrap();
+        let (_, tail) = tail.pop_front().unwrap();
+        let (_, inserted_tail) = inserted.cut(3).unwrap();
+        assert!(RefCounter::ptr_eq(&tail.head, &inserted_tail.head));
+        assert_eq!(lines.insert_at(3, "e").unwrap().get(3), Some(&"e"));
+        assert!(lines.insert_at(4, "e").is_none());
+
+        let removed = inserted.remove_at(0).unwrap();
+        assert_eq!(
+            removed.iter().map(|v| *v).collect::<Vec<_>>(),
+            ["b", "c", "d"]
+        );
+        assert_eq!(removed.length(), 3);
+        assert!(inserted.remove_at(4).is_none());
+        assert_eq!(lines.length(), 3);
+    }
+
+    #[test]
+    fn test_list_map_and_filter() {
+        let list: List<i32> = (1..=6).collect();
+        let squares = list.map(|v| v * v);
+        assert_eq!(
+            squares.iter().map(|v| *v).collect::<Vec<_>>(),
+            [1, 4, 9, 16, 25, 36]
+        );
+        let labels = list.map(|v| v.to_string());
+        assert_eq!(labels.get(5).map(String::as_str), Some("6"));
+
+        let small = list.filter(|v| *v != 2 && *v != 4);
+        assert_eq!(small.iter().map(|v| *v).collect::<Vec<_>>(), [1, 3, 5, 6]);
+        assert_eq!(small.length(), 4);
+        let (_, after_four) = list.cut(4).unwrap();
+        let (_, small_tail) = small.cut(2).unwrap();
+        assert!(RefCounter::ptr_eq(&after_four.head, &small_tail.head));
+        assert!(RefCounter::ptr_eq(&list.filter(|_| true).head, &list.head));
+        assert!(list.filter(|_| false).is_empty());
+    }
+
+    #[test]
+    fn test_list_take_and_drop() {
+        let list: List<i32> = (1..=5).collect();
+        let (front, back) = list.split_at(2);
+        assert_eq!(front.iter().map(|v| *v).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(back.iter().map(|v| *v).collect::<Vec<_>>(), [3, 4, 5]);
+        assert_eq!((front.length(), back.length()), (2, 3));
+        assert!(RefCounter::ptr_eq(&back.head, &list.drop(2).head));
+        assert!(RefCounter::ptr_eq(&list.drop(0).head, &list.head));
+        assert!(list.drop(9).is_empty());
+        assert_eq!(list.take(9).length(), 5);
+        assert!(list.take(0).is_empty());
+        let (_, second_half) = list.split();
+        assert!(RefCounter::ptr_eq(&second_half.head, &back.head));
+    }
+
+    #[test]
+    fn test_list_contains_and_position() {
+        let list = List::from(vec!["x", "y", "z"]);
+        assert!(list.contains(&"y"));
+        assert!(!list.contains(&"w"));
+        assert_eq!(list.position(|v| *v == "z"), Some(2));
+        assert!(list.position(|v| v.is_empty()).is_none());
+        assert!(!List::empty().contains(&0));
+    }
+
+    #[test]
+    fn test_list_zip() {
+        let keys = List::from(vec!["a", "b", "c"]);
+        let values = List::from(vec![1, 2]);
+        let pairs = keys.zip(&values);
+        assert_eq!(pairs.length(), 2);
+        let (key, value) = pairs.get(1).unwrap();
+        assert_eq!((**key, **value), ("b", 2));
+        assert!(RefCounter::ptr_eq(value, &values.iter().nth(1).unwrap()));
+        assert!(keys.zip(&List::<u8>::empty()).is_empty());
+    }
+
+    #[test]
+    fn test_list_sort() {
+        let list = List::from(vec![5, 3, 8, 1, 9, 2, 7]);
+        let sorted = list.sort();
+        assert_eq!(
+            sorted.iter().map(|v| *v).collect::<Vec<_>>(),
+            [1, 2, 3, 5, 7, 8, 9]
+        );
+        assert_eq!(sorted.length(), 7);
+        assert_eq!(list.get(0), Some(&5));
+        let five = list.iter().next().unwrap();
+        assert!(sorted.iter().any(|v| RefCounter::ptr_eq(&v, &five)));
+
+        let words = List::from(vec![("b", 1), ("a", 2), ("b", 0), ("a", 1)]);
+        let by_letter = words.sort_by(|x, y| x.0.cmp(y.0));
+        let order: Vec<_> = by_letter.iter().map(|v| *v).collect();
+        assert_eq!(order, [("a", 2), ("a", 1), ("b", 1), ("b", 0)]);
+        assert!(List::<u8>::empty().sort().is_empty());
+    }
+
+    #[test]
+    fn test_list_dedup() {
+        let list = List::from(vec![1, 1, 2, 3, 3, 3, 4, 5]);
+        let unique = list.dedup();
+        assert_eq!(
+            unique.iter().map(|v| *v).collect::<Vec<_>>(),
+            [1, 2, 3, 4, 5]
+        );
+        assert_eq!(unique.length(), 5);
+        assert!(RefCounter::ptr_eq(&unique.drop(3).head, &list.drop(6).head));
+        let sorted = List::from(vec![4, 1, 4, 2, 1]).sort().dedup();
+        assert_eq!(sorted.iter().map(|v| *v).collect::<Vec<_>>(), [1, 2, 4]);
+        let words = List::from(vec!["Apple", "apple", "Bee"]);
+        let folded = words.dedup_by(|a, b| a.eq_ignore_ascii_case(b));
+        assert_eq!(
+            folded.iter().map(|v| *v).collect::<Vec<_>>(),
+            ["Apple", "Bee"]
+        );
+        assert!(RefCounter::ptr_eq(&unique.dedup().head, &unique.head));
+    }
+
+    #[test]
+    fn test_list_last() {
+        let list = List::empty().push_front(3).push_front(2).push_front(1);
+        assert_eq!(list.last(), Some(&3));
+        assert_eq!(list.reverse().last(), Some(&1));
+        assert_eq!(list.drop(2).last(), Some(&3));
+        assert!(list.drop(3).last().is_none());
+        assert_eq!(list.take(2).last(), Some(&2));
+        assert_eq!(list.append(&List::from(vec![4])).last(), Some(&4));
+        assert_eq!(list.sort_by(|a, b| b.cmp(a)).last(), Some(&1));
+        assert_eq!(list.filter(|v| *v != 3).last(), Some(&2));
+        assert_eq!(List::from(vec![7]).pop_front().unwrap().1.last(), None);
+        assert!(List::<u8>::empty().last().is_none());
+    }
+
+    #[test]
+    fn test_list_traits() {
+        use std::collections::HashSet;
+
+        let list = List::from(vec![1, 2, 3]);
+        assert_eq!(format!("{:?}", list), "[1, 2, 3]");
+        assert_eq!(format!("{:?}", List::<u8>::empty()), "[]");
+        assert_eq!(list, (1..=3).collect());
+        assert_eq!(list, list.clone());
+        assert_ne!(list, List::from(vec![1, 2]));
+        assert_ne!(list, List::from(vec![1, 2, 4]));
+        assert_eq!(list.drop(1), List::from(vec![2, 3]));
+        let shared = List::from(vec![1, 2]).append(&list);
+        assert_eq!(shared.drop(2), list);
+
+        let set: HashSet<List<i32>> = [list.clone(), (1..=3).collect(), list.drop(1)]
+            .into_iter()
+            .collect();
+        assert_eq!(set.len(), 2);
+    }
+
     #[test]
     fn test_list_reverse() {
         let list = List::empty().push_front(1).push_front(2);
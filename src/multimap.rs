@@ -0,0 +1,190 @@
+use std::borrow::Borrow;
+
+use crate::{
+    avl::{AVLIterator, Compare, Natural, AVL},
+    list::List,
+    RefCounter,
+};
+
+// Values under the same key are kept most recently inserted first
+pub struct OrderedMultiMap<K, V, C = Natural> {
+    map: AVL<K, List<V>, C>,
+    len: usize,
+}
+
+impl<K, V, C> Clone for OrderedMultiMap<K, V, C> {
+    fn clone(&self) -> Self {
+        Self {
+            map: self.map.clone(),
+            len: self.len,
+        }
+    }
+}
+
+impl<K: Ord, V> OrderedMultiMap<K, V> {
+    pub fn empty() -> Self {
+        Self {
+            map: AVL::empty(),
+            len: 0,
+        }
+    }
+}
+
+impl<K, V, C: Compare<K>> OrderedMultiMap<K, V, C> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    pub fn key_count(&self) -> usize {
+        self.map.len()
+    }
+    pub fn insert(&self, key: K, value: V) -> Self {
+        Self {
+            map: self
+                .map
+                .put_with(key, List::empty().push_front(value), |values, new| {
+                    let (value_rc, _) = new.pop_front_rc().unwrap();
+                    values.push_front_rc(value_rc)
+                }),
+            len: self.len + 1,
+        }
+    }
+    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&List<V>>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.map.find(key)
+    }
+    pub fn contains_key<Q: ?Sized>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.map.find(key).is_some()
+    }
+    pub fn remove_one(&self, key: &K, value: &V) -> Self
+    where
+        K: Clone,
+        V: PartialEq,
+    {
+        let remaining = match self.map.find(key) {
+            Some(values) => Self::without_first(values, value),
+            None => None,
+        };
+        match remaining {
+            None => self.clone(),
+            Some(remaining) => Self {
+                map: self.map.update(key, |_| {
+                    if remaining.is_empty() {
+                        None
+                    } else {
+                        Some(remaining)
+                    }
+                }),
+                len: self.len - 1,
+            },
+        }
+    }
+    pub fn remove_all<Q: ?Sized>(&self, key: &Q) -> Self
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        match self.map.remove(key) {
+            Some((values, map)) => Self {
+                map,
+                len: self.len - values.length(),
+            },
+            None => self.clone(),
+        }
+    }
+    pub fn groups(&self) -> AVLIterator<'_, K, List<V>, C> {
+        self.map.iter()
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (&K, RefCounter<V>)> {
+        self.map
+            .iter()
+            .flat_map(|(key, values)| values.iter().map(move |value| (key, value)))
+    }
+
+    // Returns None when the value is not in the list
+    fn without_first(values: &List<V>, target: &V) -> Option<List<V>>
+    where
+        V: PartialEq,
+    {
+        let mut prefix = Vec::new();
+        let mut rest = values.clone();
+        loop {
+            let (value_rc, tail) = rest.pop_front_rc()?;
+            if value_rc.as_ref() == target {
+                rest = tail;
+                break;
+            }
+            prefix.push(value_rc);
+            rest = tail;
+        }
+        Some(
+            prefix
+                .into_iter()
+                .rev()
+                .fold(rest, |list, value_rc| list.push_front_rc(value_rc)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multimap_insert_and_get() {
+        let m = OrderedMultiMap::empty()
+            .insert("b", 1)
+            .insert("a", 2)
+            .insert("b", 3);
+        assert_eq!(m.len(), 3);
+        assert_eq!(m.key_count(), 2);
+        let values: Vec<_> = m.get(&"b").unwrap().iter().map(|v| *v).collect();
+        assert_eq!(values, vec![3, 1]);
+        assert!(m.contains_key(&"a"));
+        assert!(m.get(&"c").is_none());
+
+        let groups: Vec<_> = m
+            .groups()
+            .map(|(k, values)| (*k, values.length()))
+            .collect();
+        assert_eq!(groups, vec![("a", 1), ("b", 2)]);
+        let flat: Vec<_> = m.iter().map(|(k, v)| (*k, *v)).collect();
+        assert_eq!(flat, vec![("a", 2), ("b", 3), ("b", 1)]);
+    }
+
+    #[test]
+    fn test_multimap_remove() {
+        let m = OrderedMultiMap::empty()
+            .insert(1, "x")
+            .insert(1, "y")
+            .insert(1, "x")
+            .insert(2, "z");
+
+        let one_removed = m.remove_one(&1, &"x");
+        assert_eq!(one_removed.len(), 3);
+        let values: Vec<_> = one_removed.get(&1).unwrap().iter().map(|v| *v).collect();
+        assert_eq!(values, vec!["y", "x"]);
+
+        let untouched = m.remove_one(&1, &"w");
+        assert_eq!(untouched.len(), 4);
+
+        let last_removed = m.remove_one(&2, &"z");
+        assert!(!last_removed.contains_key(&2));
+        assert_eq!(last_removed.key_count(), 1);
+
+        let all_removed = m.remove_all(&1);
+        assert_eq!(all_removed.len(), 1);
+        assert!(!all_removed.contains_key(&1));
+        assert_eq!(m.len(), 4);
+        assert!(m.remove_all(&1).remove_all(&2).is_empty());
+    }
+}
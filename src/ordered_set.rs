@@ -0,0 +1,264 @@
+use std::{borrow::Borrow, fmt::Debug, hash::Hash, ops::RangeBounds};
+
+use crate::avl::{Compare, KeysIterator, Natural, AVL};
+
+pub struct OrderedSet<K, C = Natural> {
+    tree: AVL<K, (), C>,
+}
+
+impl<K, C> Clone for OrderedSet<K, C> {
+    fn clone(&self) -> Self {
+        Self {
+            tree: self.tree.clone(),
+        }
+    }
+}
+
+impl<K, C> Default for OrderedSet<K, C> {
+    fn default() -> Self {
+        Self { tree: AVL::Empty }
+    }
+}
+
+impl<K: Debug, C> Debug for OrderedSet<K, C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<K: PartialEq, C> PartialEq for OrderedSet<K, C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.tree == other.tree
+    }
+}
+
+impl<K: Eq, C> Eq for OrderedSet<K, C> {}
+
+impl<K: Hash, C> Hash for OrderedSet<K, C> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.tree.hash(state)
+    }
+}
+
+impl<K: Ord> OrderedSet<K> {
+    pub fn empty() -> Self {
+        Self { tree: AVL::empty() }
+    }
+}
+
+impl<K, C> OrderedSet<K, C> {
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+    pub fn iter(&self) -> OrderedSetIterator<'_, K, C> {
+        OrderedSetIterator {
+            inner: self.tree.keys(),
+        }
+    }
+    pub fn as_tree(&self) -> &AVL<K, (), C> {
+        &self.tree
+    }
+}
+
+impl<K, C: Compare<K>> OrderedSet<K, C> {
+    pub fn empty_ordered() -> Self {
+        Self {
+            tree: AVL::empty_ordered(),
+        }
+    }
+    pub fn insert(&self, value: K) -> Self {
+        Self {
+            tree: self.tree.insert(value),
+        }
+    }
+    pub fn insert_owned(self, value: K) -> Self {
+        Self {
+            tree: self.tree.insert_owned(value),
+        }
+    }
+    pub fn delete<Q: ?Sized>(&self, value: &Q) -> Self
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        Self {
+            tree: self.tree.delete(value),
+        }
+    }
+    pub fn search<Q: ?Sized>(&self, value: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.tree.search(value)
+    }
+    pub fn min(&self) -> Option<&K> {
+        self.tree.min().map(|(key, _)| key)
+    }
+    pub fn max(&self) -> Option<&K> {
+        self.tree.max().map(|(key, _)| key)
+    }
+    pub fn nth(&self, index: usize) -> Option<&K> {
+        self.tree.nth(index).map(|(key, _)| key)
+    }
+    pub fn rank<Q: ?Sized>(&self, value: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.tree.rank(value)
+    }
+    pub fn count_range<Q: ?Sized, R: RangeBounds<Q>>(&self, range: R) -> usize
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.tree.count_range(range)
+    }
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            tree: self.tree.union(&other.tree, |_, _| ()),
+        }
+    }
+    pub fn intersection(&self, other: &Self) -> Self {
+        Self {
+            tree: self.tree.intersection(&other.tree, |_, _| ()),
+        }
+    }
+    pub fn difference(&self, other: &Self) -> Self {
+        Self {
+            tree: self.tree.difference(&other.tree),
+        }
+    }
+    pub fn symmetric_difference(&self, other: &Self) -> Self {
+        self.difference(other).union(&other.difference(self))
+    }
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.len() <= other.len() && self.tree.difference(&other.tree).is_empty()
+    }
+    pub fn is_superset(&self, other: &Self) -> bool {
+        other.is_subset(self)
+    }
+    pub fn is_disjoint(&self, other: &Self) -> bool {
+        self.intersection(other).is_empty()
+    }
+}
+
+impl<K, C> From<AVL<K, (), C>> for OrderedSet<K, C> {
+    fn from(tree: AVL<K, (), C>) -> Self {
+        Self { tree }
+    }
+}
+
+impl<K, C: Compare<K>> FromIterator<K> for OrderedSet<K, C> {
+    fn from_iter<I: IntoIterator<Item = K>>(iter: I) -> Self {
+        Self {
+            tree: iter.into_iter().collect(),
+        }
+    }
+}
+
+pub struct OrderedSetIterator<'a, K, C = Natural> {
+    inner: KeysIterator<'a, K, (), C>,
+}
+
+impl<'a, K, C> Iterator for OrderedSetIterator<'a, K, C> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, C> DoubleEndedIterator for OrderedSetIterator<'a, K, C> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back()
+    }
+}
+
+impl<'a, K, C> ExactSizeIterator for OrderedSetIterator<'a, K, C> {}
+
+impl<'a, K, C> IntoIterator for &'a OrderedSet<K, C> {
+    type Item = &'a K;
+    type IntoIter = OrderedSetIterator<'a, K, C>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::avl::Reverse;
+
+    #[test]
+    fn test_ordered_set_basics() {
+        let s: OrderedSet<_> = (0..100).rev().collect();
+        assert_eq!(s.len(), 100);
+        assert!(s.search(&42));
+        assert!(!s.search(&100));
+        assert!(s.iter().copied().eq(0..100));
+        assert_eq!(s.iter().next_back(), Some(&99));
+        assert_eq!((s.min(), s.max()), (Some(&0), Some(&99)));
+        assert_eq!(s.nth(10), Some(&10));
+        assert_eq!(s.rank(&10), 10);
+        assert_eq!(s.count_range(10..20), 10);
+
+        let s = s.insert(7).insert(200).delete(&0);
+        assert_eq!(s.len(), 100);
+        assert!(!s.search(&0));
+        assert_eq!(
+            format!("{:?}", OrderedSet::empty().insert(2).insert(1)),
+            "{1, 2}"
+        );
+
+        let reversed: OrderedSet<_, Reverse> = (0..10).collect();
+        assert!(reversed.iter().copied().eq((0..10).rev()));
+        let descending = OrderedSet::<_, Reverse>::empty_ordered()
+            .insert(1)
+            .insert(2);
+        assert!(descending.iter().copied().eq([2, 1]));
+        let built = (0..10).fold(OrderedSet::empty(), |s, i| s.insert_owned(i));
+        assert!(built.iter().copied().eq(0..10));
+    }
+
+    #[test]
+    fn test_ordered_set_algebra() {
+        let evens: OrderedSet<i32> = (0..20).step_by(2).collect();
+        let small: OrderedSet<i32> = (0..10).collect();
+        assert!(evens
+            .union(&small)
+            .iter()
+            .copied()
+            .eq((0..10).chain((10..20).step_by(2))));
+        assert!(evens
+            .intersection(&small)
+            .iter()
+            .copied()
+            .eq((0..10).step_by(2)));
+        assert!(evens
+            .difference(&small)
+            .iter()
+            .copied()
+            .eq((10..20).step_by(2)));
+        let symmetric: Vec<_> = evens.symmetric_difference(&small).iter().copied().collect();
+        assert_eq!(symmetric, vec![1, 3, 5, 7, 9, 10, 12, 14, 16, 18]);
+
+        let tiny: OrderedSet<i32> = [2, 4].into_iter().collect();
+        assert!(tiny.is_subset(&evens));
+        assert!(tiny.is_subset(&small));
+        assert!(!small.is_subset(&evens));
+        assert!(evens.is_superset(&tiny));
+        assert!(OrderedSet::empty().is_subset(&tiny));
+        assert!(!evens.is_disjoint(&small));
+        assert!(tiny.is_disjoint(&[1, 3].into_iter().collect()));
+    }
+}
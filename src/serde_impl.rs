@@ -0,0 +1,231 @@
+use std::{fmt, marker::PhantomData};
+
+use serde::{
+    de::{MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{
+    avl::{Compare, AVL},
+    list::List,
+    ordered_set::OrderedSet,
+    trie::Trie,
+};
+
+impl<K: Serialize, V: Serialize, C> Serialize for AVL<K, V, C> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.iter())
+    }
+}
+
+struct AVLVisitor<K, V, C> {
+    phantom: PhantomData<(K, V, C)>,
+}
+
+impl<'de, K: Deserialize<'de>, V: Deserialize<'de>, C: Compare<K>> Visitor<'de>
+    for AVLVisitor<K, V, C>
+{
+    type Value = AVL<K, V, C>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut entries = Vec::with_capacity(map.size_hint().unwrap_or(0));
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(entries.into_iter().collect())
+    }
+}
+
+impl<'de, K: Deserialize<'de>, V: Deserialize<'de>, C: Compare<K>> Deserialize<'de>
+    for AVL<K, V, C>
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(AVLVisitor {
+            phantom: PhantomData,
+        })
+    }
+}
+
+impl<K: Serialize, C> Serialize for OrderedSet<K, C> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+struct OrderedSetVisitor<K, C> {
+    phantom: PhantomData<(K, C)>,
+}
+
+impl<'de, K: Deserialize<'de>, C: Compare<K>> Visitor<'de> for OrderedSetVisitor<K, C> {
+    type Value = OrderedSet<K, C>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+        Ok(values.into_iter().collect())
+    }
+}
+
+impl<'de, K: Deserialize<'de>, C: Compare<K>> Deserialize<'de> for OrderedSet<K, C> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_seq(OrderedSetVisitor {
+            phantom: PhantomData,
+        })
+    }
+}
+
+// A sequence of (key, values) pairs in iteration order rather than a map, as
+// formats like JSON only accept strings as map keys
+impl<T: Serialize + PartialEq + Clone, U: Serialize> Serialize for Trie<T, U> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut entries: Vec<(Vec<T>, Vec<&U>)> = Vec::with_capacity(self.key_count());
+        for (key, value) in self.iter() {
+            match entries.last_mut() {
+                Some((last, values)) if *last == key => values.push(value),
+                _ => entries.push((key, vec![value])),
+            }
+        }
+        serializer.collect_seq(entries)
+    }
+}
+
+struct TrieVisitor<T, U> {
+    phantom: PhantomData<(T, U)>,
+}
+
+impl<'de, T: Deserialize<'de> + PartialEq + Clone, U: Deserialize<'de>> Visitor<'de>
+    for TrieVisitor<T, U>
+{
+    type Value = Trie<T, U>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence of keys with their values")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut entries = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some((key, values)) = seq.next_element::<(Vec<T>, Vec<U>)>()? {
+            for value in values {
+                entries.push((key.clone(), value));
+            }
+        }
+        Ok(Trie::from_sorted(entries))
+    }
+}
+
+impl<'de, T: Deserialize<'de> + PartialEq + Clone, U: Deserialize<'de>> Deserialize<'de>
+    for Trie<T, U>
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_seq(TrieVisitor {
+            phantom: PhantomData,
+        })
+    }
+}
+
+impl<T: Serialize> Serialize for List<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter_ref())
+    }
+}
+
+struct ListVisitor<T> {
+    phantom: PhantomData<T>,
+}
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for ListVisitor<T> {
+    type Value = List<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+        Ok(List::from(values))
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for List<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_seq(ListVisitor {
+            phantom: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{avl::OrderedMap, list::List, ordered_set::OrderedSet, trie::Trie};
+
+    #[test]
+    fn avl_round_trip() {
+        let m: OrderedMap<_, _> = vec![(3, "c".to_string()), (1, "a".to_string())]
+            .into_iter()
+            .collect();
+        let json = serde_json::to_string(&m).unwrap();
+        assert_eq!(json, r#"{"1":"a","3":"c"}"#);
+        let back: OrderedMap<i32, String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, m);
+
+        let s: OrderedSet<_> = (0..10).rev().collect();
+        let json = serde_json::to_string(&s).unwrap();
+        assert_eq!(json, "[0,1,2,3,4,5,6,7,8,9]");
+        let back: OrderedSet<i32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, s);
+        assert_eq!(back.len(), 10);
+    }
+
+    #[test]
+    fn trie_round_trip() {
+        let t = Trie::empty_store()
+            .insert_store("ab", 1)
+            .insert_store("a", 2)
+            .insert_store("ab", 3);
+        let json = serde_json::to_string(&t).unwrap();
+        assert_eq!(json, "[[[97],[2]],[[97,98],[1,3]]]");
+        let back: Trie<u8, i32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(&*back.get_store("ab").unwrap(), &[&1, &3]);
+        assert_eq!((back.len(), back.key_count()), (3, 2));
+
+        let routes = Trie::empty_store()
+            .insert_store(vec!["users".to_string()], "list".to_string())
+            .insert_store(
+                vec!["users".to_string(), "new".to_string()],
+                "new".to_string(),
+            );
+        let json = serde_json::to_string(&routes).unwrap();
+        let back: Trie<String, String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            &*back
+                .get_store(["users".to_string(), "new".to_string()])
+                .unwrap(),
+            &[&"new".to_string()]
+        );
+        assert!(serde_json::from_str::<Trie<u8, i32>>("{}").is_err());
+    }
+
+    #[test]
+    fn list_round_trip() {
+        let l = List::from(vec!["x".to_string(), "y".to_string(), "z".to_string()]);
+        let json = serde_json::to_string(&l).unwrap();
+        assert_eq!(json, r#"["x","y","z"]"#);
+        let back: List<String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, l);
+        assert_eq!(back.last().map(String::as_str), Some("z"));
+        assert!(serde_json::from_str::<List<i32>>("[]").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,103 @@
+use std::fmt::Debug;
+
+use proptest::{
+    arbitrary::{any, Arbitrary},
+    collection::{vec, SizeRange},
+    prop_oneof,
+    strategy::{BoxedStrategy, Just, Strategy},
+};
+
+use crate::avl::AVL;
+
+#[derive(Debug, Clone)]
+enum Edit<K, V> {
+    Put(K, V),
+    Delete(K),
+}
+
+impl<K: Ord, V> Edit<K, V> {
+    fn apply(self, tree: &AVL<K, V>) -> AVL<K, V> {
+        match self {
+            Edit::Put(key, value) => tree.put(key, value),
+            Edit::Delete(key) => tree.delete(&key),
+        }
+    }
+}
+
+// Trees built by putting the entries one at a time in the generated order, so
+// the shapes are the ones real insertion sequences produce
+pub fn avl<K, V>(
+    key: impl Strategy<Value = K>,
+    value: impl Strategy<Value = V>,
+    size: impl Into<SizeRange>,
+) -> impl Strategy<Value = AVL<K, V>>
+where
+    K: Ord + Debug,
+    V: Debug,
+{
+    vec((key, value), size).prop_map(|entries| {
+        entries
+            .into_iter()
+            .fold(AVL::empty(), |tree, (key, value)| tree.put(key, value))
+    })
+}
+
+// A base tree followed by versions derived from it through single puts and
+// deletes, each one sharing most of its nodes with the previous version
+pub fn avl_versions<K, V>(
+    key: impl Strategy<Value = K> + Clone,
+    value: impl Strategy<Value = V> + Clone,
+    size: impl Into<SizeRange>,
+    edits: impl Into<SizeRange>,
+) -> impl Strategy<Value = Vec<AVL<K, V>>>
+where
+    K: Ord + Clone + Debug,
+    V: Clone + Debug,
+{
+    let edit = prop_oneof![
+        (key.clone(), value.clone()).prop_map(|(key, value)| Edit::Put(key, value)),
+        key.clone().prop_map(Edit::Delete),
+    ];
+    (avl(key, value, size), vec(edit, edits)).prop_map(|(base, edits)| {
+        let mut versions = vec![base];
+        for edit in edits {
+            let next = edit.apply(versions.last().unwrap());
+            versions.push(next);
+        }
+        versions
+    })
+}
+
+impl<K, V> Arbitrary for AVL<K, V>
+where
+    K: Arbitrary + Ord + 'static,
+    V: Arbitrary + 'static,
+{
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        prop_oneof![Just(AVL::empty()), avl(any::<K>(), any::<V>(), 0..100)].boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::{prop_assert, prop_assert_eq, proptest};
+
+    proptest! {
+        #[test]
+        fn generated_trees_are_valid(tree in any::<AVL<u8, i32>>()) {
+            prop_assert_eq!(tree.validate(), Ok(()));
+        }
+
+        #[test]
+        fn versions_share_structure(versions in avl_versions(0..50u32, any::<u8>(), 0..40, 1..20)) {
+            for pair in versions.windows(2) {
+                prop_assert!(pair[1].validate().is_ok());
+                prop_assert!(pair[0].diff(&pair[1]).count() <= 1);
+            }
+        }
+    }
+}
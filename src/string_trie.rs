@@ -0,0 +1,195 @@
+use std::fmt::Debug;
+
+use crate::trie::{Trie, TrieIterator};
+
+// Trie keyed by text. Keys are split into chars, so every key that comes
+// back out is valid UTF-8 again
+pub struct StringTrie<U = bool> {
+    trie: Trie<char, U>,
+}
+
+impl<U> Clone for StringTrie<U> {
+    fn clone(&self) -> Self {
+        Self {
+            trie: self.trie.clone(),
+        }
+    }
+}
+
+impl<U> Default for StringTrie<U> {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<U: Debug> Debug for StringTrie<U> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut entries: Vec<(String, Vec<&U>)> = Vec::new();
+        for (key, value) in self.iter() {
+            match entries.last_mut() {
+                Some((last, values)) if *last == key => values.push(value),
+                _ => entries.push((key, vec![value])),
+            }
+        }
+        f.debug_map().entries(entries).finish()
+    }
+}
+
+fn chars(key: &str) -> Vec<char> {
+    key.chars().collect()
+}
+
+impl<U> StringTrie<U> {
+    pub fn empty() -> Self {
+        Self {
+            trie: Trie::empty(),
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.trie.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.trie.is_empty()
+    }
+    pub fn key_count(&self) -> usize {
+        self.trie.key_count()
+    }
+    pub fn as_trie(&self) -> &Trie<char, U> {
+        &self.trie
+    }
+    pub fn insert_store(&self, key: &str, store: U) -> Self {
+        Self {
+            trie: self.trie.insert_store(chars(key), store),
+        }
+    }
+    pub fn get_store(&self, key: &str) -> Option<Box<[&U]>> {
+        self.trie.get_store(chars(key))
+    }
+    pub fn delete_key(&self, key: &str) -> Option<Self> {
+        let trie = self.trie.delete_key(chars(key))?;
+        Some(Self { trie })
+    }
+    pub fn contains_prefix(&self, prefix: &str) -> bool {
+        self.trie.contains_prefix(chars(prefix))
+    }
+    // Everything under the prefix, with the prefix taken off the keys
+    pub fn subtrie(&self, prefix: &str) -> Option<Self> {
+        let trie = self.trie.subtrie(chars(prefix))?;
+        Some(Self { trie })
+    }
+    // Longest prefix of the query that has stored values, as a slice of it
+    pub fn longest_prefix<'a>(&self, query: &'a str) -> Option<(&'a str, Box<[&U]>)> {
+        let (len, values) = self.trie.longest_prefix(chars(query))?;
+        let end = query
+            .char_indices()
+            .nth(len)
+            .map_or(query.len(), |(index, _)| index);
+        Some((&query[..end], values))
+    }
+    pub fn iter(&self) -> StringTrieIterator<'_, U> {
+        StringTrieIterator {
+            inner: self.trie.iter(),
+        }
+    }
+    pub fn keys(&self) -> impl Iterator<Item = String> + '_ {
+        self.trie.keys().map(|key| key.into_iter().collect())
+    }
+    // Keys in alphabetical order, by char
+    pub fn iter_sorted(&self) -> StringTrieIterator<'_, U> {
+        StringTrieIterator {
+            inner: self.trie.iter_sorted(),
+        }
+    }
+}
+
+impl<U: PartialEq> StringTrie<U> {
+    pub fn delete_store(&self, key: &str, store: &U) -> Option<Self> {
+        let trie = self.trie.delete_store(chars(key), store)?;
+        Some(Self { trie })
+    }
+}
+
+impl StringTrie {
+    pub fn insert(&self, key: &str) -> Self {
+        self.insert_store(key, true)
+    }
+    pub fn search(&self, key: &str) -> bool {
+        self.get_store(key).is_some()
+    }
+    pub fn delete(&self, key: &str) -> Option<Self> {
+        self.delete_store(key, &true)
+    }
+}
+
+impl<'a, U> FromIterator<(&'a str, U)> for StringTrie<U> {
+    fn from_iter<I: IntoIterator<Item = (&'a str, U)>>(iter: I) -> Self {
+        Self {
+            trie: iter
+                .into_iter()
+                .map(|(key, value)| (chars(key), value))
+                .collect(),
+        }
+    }
+}
+
+impl<U> From<Trie<char, U>> for StringTrie<U> {
+    fn from(trie: Trie<char, U>) -> Self {
+        Self { trie }
+    }
+}
+
+pub struct StringTrieIterator<'a, U> {
+    inner: TrieIterator<'a, char, U>,
+}
+
+impl<'a, U> Iterator for StringTrieIterator<'a, U> {
+    type Item = (String, &'a U);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, value) = self.inner.next()?;
+        Some((key.into_iter().collect(), value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_string_trie_unicode_keys() {
+        let t = StringTrie::empty()
+            .insert_store("café", 1)
+            .insert_store("cafés", 2)
+            .insert_store("日本", 3)
+            .insert_store("日本語", 4);
+        assert_eq!(&*t.get_store("café").unwrap(), &[&1]);
+        assert!(t.get_store("caf").is_none());
+        assert!(t.contains_prefix("日"));
+        assert_eq!((t.len(), t.key_count()), (4, 4));
+
+        let (matched, values) = t.longest_prefix("日本語の本").unwrap();
+        assert_eq!(matched, "日本語");
+        assert_eq!(&*values, &[&4]);
+        assert_eq!(t.longest_prefix("cafés").unwrap().0, "cafés");
+
+        let keys: Vec<String> = t.iter().map(|(key, _)| key).collect();
+        assert_eq!(keys, vec!["café", "cafés", "日本", "日本語"]);
+        assert_eq!(t.keys().collect::<Vec<_>>(), keys);
+        let japanese = t.subtrie("日本").unwrap();
+        assert_eq!(&*japanese.get_store("語").unwrap(), &[&4]);
+        assert_eq!(format!("{:?}", japanese), r#"{"": [3], "語": [4]}"#);
+    }
+
+    #[test]
+    fn test_string_trie_set() {
+        let t: StringTrie = [("naïve", true), ("naive", true)].into_iter().collect();
+        assert!(t.search("naïve") && t.search("naive"));
+        let removed = t.delete("naïve").unwrap();
+        assert!(!removed.search("naïve"));
+        assert!(removed.search("naive"));
+        assert!(removed.delete("naïve").is_none());
+        assert!(t.search("naïve"));
+        let sorted: Vec<String> = t.iter_sorted().map(|(key, _)| key).collect();
+        assert_eq!(sorted, vec!["naive", "naïve"]);
+    }
+}
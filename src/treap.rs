@@ -0,0 +1,666 @@
+use std::{
+    borrow::Borrow,
+    cell::Cell,
+    cmp::Ordering,
+    collections::hash_map::RandomState,
+    hash::BuildHasher,
+    ops::{Bound, RangeBounds},
+};
+
+use crate::RefCounter;
+
+// Binary search tree on the keys and max-heap on the random priorities, which
+// keeps it balanced in expectation without any rotations bookkeeping
+pub enum Treap<K, V = ()> {
+    Empty,
+    Node {
+        key: RefCounter<K>,
+        value: RefCounter<V>,
+        left: RefCounter<Treap<K, V>>,
+        right: RefCounter<Treap<K, V>>,
+        size: usize,
+        priority: u64,
+    },
+}
+
+type Split<K, V> = (Treap<K, V>, Option<Treap<K, V>>, Treap<K, V>);
+
+thread_local! {
+    static SEED: Cell<u64> = Cell::new(RandomState::new().hash_one(0u64) | 1);
+}
+
+// xorshift64
+fn random_priority() -> u64 {
+    SEED.with(|seed| {
+        let mut x = seed.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        seed.set(x);
+        x
+    })
+}
+
+impl<K, V> Clone for Treap<K, V> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Empty => Self::Empty,
+            Self::Node {
+                key,
+                value,
+                left,
+                right,
+                size,
+                priority,
+            } => Self::Node {
+                key: key.clone(),
+                value: value.clone(),
+                left: left.clone(),
+                right: right.clone(),
+                size: *size,
+                priority: *priority,
+            },
+        }
+    }
+}
+
+impl<K, V> Treap<K, V> {
+    fn node(
+        key: RefCounter<K>,
+        value: RefCounter<V>,
+        left: RefCounter<Treap<K, V>>,
+        right: RefCounter<Treap<K, V>>,
+        priority: u64,
+    ) -> Treap<K, V> {
+        let size = 1 + left.len() + right.len();
+        Treap::Node {
+            key,
+            value,
+            left,
+            right,
+            size,
+            priority,
+        }
+    }
+    pub fn len(&self) -> usize {
+        match self {
+            Treap::Empty => 0,
+            Treap::Node { size, .. } => *size,
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        matches!(self, Treap::Empty)
+    }
+    pub fn iter(&self) -> TreapIterator<'_, K, V> {
+        let mut iter = TreapIterator {
+            stack: Vec::new(),
+            remaining: self.len(),
+        };
+        iter.push_left_spine(self);
+        iter
+    }
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(key, _)| key)
+    }
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, value)| value)
+    }
+    pub fn min(&self) -> Option<(&K, &V)> {
+        let mut node = self;
+        let mut found = None;
+        while let Treap::Node {
+            key, value, left, ..
+        } = node
+        {
+            found = Some((key.as_ref(), value.as_ref()));
+            node = left;
+        }
+        found
+    }
+    pub fn max(&self) -> Option<(&K, &V)> {
+        let mut node = self;
+        let mut found = None;
+        while let Treap::Node {
+            key, value, right, ..
+        } = node
+        {
+            found = Some((key.as_ref(), value.as_ref()));
+            node = right;
+        }
+        found
+    }
+    // Entry at the given position in key order
+    pub fn nth(&self, mut index: usize) -> Option<(&K, &V)> {
+        let mut node = self;
+        while let Treap::Node {
+            key,
+            value,
+            left,
+            right,
+            ..
+        } = node
+        {
+            node = match index.cmp(&left.len()) {
+                Ordering::Less => left,
+                Ordering::Equal => return Some((key.as_ref(), value.as_ref())),
+                Ordering::Greater => {
+                    index -= left.len() + 1;
+                    right
+                }
+            };
+        }
+        None
+    }
+    // Concatenates two treaps, every key of self must be smaller than every
+    // key of other
+    fn concat(&self, other: &Treap<K, V>) -> Treap<K, V> {
+        match (self, other) {
+            (Treap::Empty, _) => other.clone(),
+            (_, Treap::Empty) => self.clone(),
+            (
+                Treap::Node {
+                    key: lk,
+                    value: lv,
+                    left: ll,
+                    right: lr,
+                    priority: lp,
+                    ..
+                },
+                Treap::Node {
+                    key: rk,
+                    value: rv,
+                    left: rl,
+                    right: rr,
+                    priority: rp,
+                    ..
+                },
+            ) => {
+                if lp >= rp {
+                    Treap::node(
+                        lk.clone(),
+                        lv.clone(),
+                        ll.clone(),
+                        RefCounter::new(lr.concat(other)),
+                        *lp,
+                    )
+                } else {
+                    Treap::node(
+                        rk.clone(),
+                        rv.clone(),
+                        RefCounter::new(self.concat(rl)),
+                        rr.clone(),
+                        *rp,
+                    )
+                }
+            }
+        }
+    }
+}
+
+impl<K: Ord, V> Treap<K, V> {
+    pub fn empty() -> Treap<K, V> {
+        Treap::Empty
+    }
+    pub fn find<Q: ?Sized + Ord>(&self, target_key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+    {
+        let mut node = self;
+        while let Treap::Node {
+            key,
+            value,
+            left,
+            right,
+            ..
+        } = node
+        {
+            node = match target_key.cmp((**key).borrow()) {
+                Ordering::Less => left,
+                Ordering::Equal => return Some(value),
+                Ordering::Greater => right,
+            };
+        }
+        None
+    }
+    // Number of keys smaller than the given one, which is the position it
+    // has or would have
+    pub fn rank<Q: ?Sized + Ord>(&self, target_key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+    {
+        self.count_below(target_key, false)
+    }
+    pub fn count_range<Q: ?Sized + Ord, R: RangeBounds<Q>>(&self, range: R) -> usize
+    where
+        K: Borrow<Q>,
+    {
+        let below_end = match range.end_bound() {
+            Bound::Included(end) => self.count_below(end, true),
+            Bound::Excluded(end) => self.count_below(end, false),
+            Bound::Unbounded => self.len(),
+        };
+        let below_start = match range.start_bound() {
+            Bound::Included(start) => self.count_below(start, false),
+            Bound::Excluded(start) => self.count_below(start, true),
+            Bound::Unbounded => 0,
+        };
+        below_end.saturating_sub(below_start)
+    }
+    // Number of keys smaller than (or, if inclusive, equal to) the bound
+    fn count_below<Q: ?Sized + Ord>(&self, bound: &Q, inclusive: bool) -> usize
+    where
+        K: Borrow<Q>,
+    {
+        let mut count = 0;
+        let mut node = self;
+        while let Treap::Node {
+            key, left, right, ..
+        } = node
+        {
+            node = match bound.cmp((**key).borrow()) {
+                Ordering::Less => left,
+                Ordering::Equal if !inclusive => return count + left.len(),
+                Ordering::Equal => return count + left.len() + 1,
+                Ordering::Greater => {
+                    count += left.len() + 1;
+                    right
+                }
+            };
+        }
+        count
+    }
+    // In-order iteration starting at the first key not smaller than the bound
+    pub fn iter_from<Q: ?Sized + Ord>(&self, bound: &Q) -> TreapIterator<'_, K, V>
+    where
+        K: Borrow<Q>,
+    {
+        let mut iter = TreapIterator {
+            stack: Vec::new(),
+            remaining: self.len() - self.rank(bound),
+        };
+        let mut node = self;
+        while let Treap::Node {
+            key, left, right, ..
+        } = node
+        {
+            node = match bound.cmp((**key).borrow()) {
+                Ordering::Greater => right,
+                _ => {
+                    iter.stack.push(node);
+                    left
+                }
+            };
+        }
+        iter
+    }
+    // Concatenates two treaps. None when some key of self is not smaller
+    // than every key of other, as the result would be out of order
+    pub fn merge(&self, other: &Treap<K, V>) -> Option<Treap<K, V>> {
+        match (self.max(), other.min()) {
+            (Some((last, _)), Some((first, _))) if last >= first => None,
+            _ => Some(self.concat(other)),
+        }
+    }
+    pub fn put(&self, key: K, value: V) -> Treap<K, V> {
+        let (less, found, greater) = self.split_three(&key);
+        let priority = match found {
+            Some(Treap::Node { priority, .. }) => priority,
+            _ => random_priority(),
+        };
+        let single = Treap::node(
+            RefCounter::new(key),
+            RefCounter::new(value),
+            RefCounter::new(Treap::Empty),
+            RefCounter::new(Treap::Empty),
+            priority,
+        );
+        less.concat(&single).concat(&greater)
+    }
+    pub fn delete<Q: ?Sized + Ord>(&self, target_key: &Q) -> Treap<K, V>
+    where
+        K: Borrow<Q>,
+    {
+        match self.remove(target_key) {
+            Some((_, deleted)) => deleted,
+            None => self.clone(),
+        }
+    }
+    pub fn remove<Q: ?Sized + Ord>(&self, target_key: &Q) -> Option<(RefCounter<V>, Treap<K, V>)>
+    where
+        K: Borrow<Q>,
+    {
+        match self.split_three(target_key) {
+            (less, Some(Treap::Node { value, .. }), greater) => {
+                Some((value, less.concat(&greater)))
+            }
+            _ => None,
+        }
+    }
+    // Same contract as AVL::update: f sees the current value, if any, and
+    // returns the new one or None to remove the key
+    pub fn update<F>(&self, target_key: &K, f: F) -> Treap<K, V>
+    where
+        K: Clone,
+        F: FnOnce(Option<&V>) -> Option<V>,
+    {
+        let (less, found, greater) = self.split_three(target_key);
+        let (key, priority, new_value) = match &found {
+            Some(Treap::Node {
+                key,
+                value,
+                priority,
+                ..
+            }) => (key.clone(), *priority, f(Some(value))),
+            _ => match f(None) {
+                Some(value) => (
+                    RefCounter::new(target_key.clone()),
+                    random_priority(),
+                    Some(value),
+                ),
+                None => return self.clone(),
+            },
+        };
+        match new_value {
+            Some(value) => {
+                let single = Treap::node(
+                    key,
+                    RefCounter::new(value),
+                    RefCounter::new(Treap::Empty),
+                    RefCounter::new(Treap::Empty),
+                    priority,
+                );
+                less.concat(&single).concat(&greater)
+            }
+            None => less.concat(&greater),
+        }
+    }
+    pub fn pop_min(&self) -> Option<(&K, &V, Treap<K, V>)> {
+        let (key, value) = self.min()?;
+        Some((key, value, self.delete(key)))
+    }
+    pub fn pop_max(&self) -> Option<(&K, &V, Treap<K, V>)> {
+        let (key, value) = self.max()?;
+        Some((key, value, self.delete(key)))
+    }
+    pub fn delete_range<Q: ?Sized + Ord, R: RangeBounds<Q>>(&self, range: R) -> Treap<K, V>
+    where
+        K: Borrow<Q>,
+    {
+        if self.count_range((range.start_bound(), range.end_bound())) == 0 {
+            return self.clone();
+        }
+        let before = match range.start_bound() {
+            Bound::Included(start) => self.split_at_bound(start, false).0,
+            Bound::Excluded(start) => self.split_at_bound(start, true).0,
+            Bound::Unbounded => Treap::Empty,
+        };
+        let after = match range.end_bound() {
+            Bound::Included(end) => self.split_at_bound(end, true).1,
+            Bound::Excluded(end) => self.split_at_bound(end, false).1,
+            Bound::Unbounded => Treap::Empty,
+        };
+        before.concat(&after)
+    }
+    // Keys smaller than the bound go left, the rest go right
+    pub fn split<Q: ?Sized + Ord>(&self, bound: &Q) -> (Treap<K, V>, Treap<K, V>)
+    where
+        K: Borrow<Q>,
+    {
+        self.split_at_bound(bound, false)
+    }
+    // Like split, with a key equal to the bound going left when inclusive
+    fn split_at_bound<Q: ?Sized + Ord>(
+        &self,
+        bound: &Q,
+        inclusive: bool,
+    ) -> (Treap<K, V>, Treap<K, V>)
+    where
+        K: Borrow<Q>,
+    {
+        let (less, found, greater) = self.split_three(bound);
+        match found {
+            Some(single) if inclusive => (less.concat(&single), greater),
+            Some(single) => (less, single.concat(&greater)),
+            None => (less, greater),
+        }
+    }
+    // Separates the keys below the bound, the node equal to it (as a single
+    // node treap) and the keys above it
+    fn split_three<Q: ?Sized + Ord>(&self, bound: &Q) -> Split<K, V>
+    where
+        K: Borrow<Q>,
+    {
+        match self {
+            Treap::Empty => (Treap::Empty, None, Treap::Empty),
+            Treap::Node {
+                key,
+                value,
+                left,
+                right,
+                priority,
+                ..
+            } => match bound.cmp((**key).borrow()) {
+                Ordering::Less => {
+                    let (less, found, greater) = left.split_three(bound);
+                    let greater = Treap::node(
+                        key.clone(),
+                        value.clone(),
+                        RefCounter::new(greater),
+                        right.clone(),
+                        *priority,
+                    );
+                    (less, found, greater)
+                }
+                Ordering::Equal => {
+                    let single = Treap::node(
+                        key.clone(),
+                        value.clone(),
+                        RefCounter::new(Treap::Empty),
+                        RefCounter::new(Treap::Empty),
+                        *priority,
+                    );
+                    (left.as_ref().clone(), Some(single), right.as_ref().clone())
+                }
+                Ordering::Greater => {
+                    let (less, found, greater) = right.split_three(bound);
+                    let less = Treap::node(
+                        key.clone(),
+                        value.clone(),
+                        left.clone(),
+                        RefCounter::new(less),
+                        *priority,
+                    );
+                    (less, found, greater)
+                }
+            },
+        }
+    }
+}
+
+impl<K: Ord> Treap<K, ()> {
+    pub fn insert(&self, value: K) -> Self {
+        self.put(value, ())
+    }
+    pub fn search<Q: ?Sized + Ord>(&self, value: &Q) -> bool
+    where
+        K: Borrow<Q>,
+    {
+        self.find(value).is_some()
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for Treap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        iter.into_iter()
+            .fold(Treap::empty(), |treap, (key, value)| treap.put(key, value))
+    }
+}
+
+pub struct TreapIterator<'a, K, V> {
+    stack: Vec<&'a Treap<K, V>>,
+    remaining: usize,
+}
+
+impl<'a, K, V> TreapIterator<'a, K, V> {
+    fn push_left_spine(&mut self, mut node: &'a Treap<K, V>) {
+        while let Treap::Node { left, .. } = node {
+            self.stack.push(node);
+            node = left;
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for TreapIterator<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.stack.pop()? {
+            Treap::Empty => None,
+            Treap::Node {
+                key, value, right, ..
+            } => {
+                self.remaining -= 1;
+                self.push_left_spine(right);
+                Some((key.as_ref(), value.as_ref()))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for TreapIterator<'a, K, V> {}
+
+impl<'a, K, V> IntoIterator for &'a Treap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = TreapIterator<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_treap<K: Ord, V>(t: &Treap<K, V>) -> bool {
+        match t {
+            Treap::Empty => true,
+            Treap::Node {
+                key,
+                left,
+                right,
+                size,
+                priority,
+                ..
+            } => {
+                let heap_ordered = [left, right].iter().all(|child| match child.as_ref() {
+                    Treap::Empty => true,
+                    Treap::Node { priority: p, .. } => p <= priority,
+                });
+                let keys_ordered = left.keys().all(|k| k < key) && right.keys().all(|k| k > key);
+                heap_ordered
+                    && keys_ordered
+                    && *size == 1 + left.len() + right.len()
+                    && is_treap(left)
+                    && is_treap(right)
+            }
+        }
+    }
+
+    #[test]
+    fn test_treap_put_find_delete() {
+        let t: Treap<i32, i32> = (0..500).map(|i| ((i * 37) % 500, i)).collect();
+        assert_eq!(t.len(), 500);
+        assert!(is_treap(&t));
+        assert!(t.keys().copied().eq(0..500));
+        assert_eq!(t.find(&37), Some(&1));
+
+        let updated = t.put(37, -1);
+        assert_eq!(updated.len(), 500);
+        assert_eq!(updated.find(&37), Some(&-1));
+        assert_eq!(t.find(&37), Some(&1));
+
+        let removed = (0..250).fold(updated, |t, i| t.delete(&(i * 2)));
+        assert_eq!(removed.len(), 250);
+        assert!(is_treap(&removed));
+        assert!(removed.keys().copied().eq((1..500).step_by(2)));
+        assert_eq!(removed.delete(&0).len(), 250);
+
+        let set = Treap::empty().insert("b").insert("a").insert("b");
+        assert_eq!(set.len(), 2);
+        assert!(set.search("a"));
+        assert!(!set.search("c"));
+    }
+
+    #[test]
+    fn test_treap_split_merge() {
+        let t: Treap<i32, ()> = (0..300).map(|i| (i, ())).collect();
+        let (low, high) = t.split(&120);
+        assert!(low.keys().copied().eq(0..120));
+        assert!(high.keys().copied().eq(120..300));
+        assert!(is_treap(&low) && is_treap(&high));
+        let (_, missing) = t.split(&1000);
+        assert!(missing.is_empty());
+
+        let joined = low.merge(&high).unwrap();
+        assert_eq!(joined.len(), 300);
+        assert!(is_treap(&joined));
+        assert!(joined.keys().copied().eq(0..300));
+        assert!(Treap::<i32>::empty()
+            .merge(&high)
+            .unwrap()
+            .keys()
+            .eq(high.keys()));
+        assert!(high.merge(&low).is_none());
+        assert!(joined.merge(&high).is_none());
+    }
+
+    #[test]
+    fn test_treap_positional() {
+        let lines: Treap<u32, &str> = [(10, "a"), (20, "b"), (30, "c"), (40, "d"), (50, "e")]
+            .into_iter()
+            .collect();
+        assert_eq!(lines.nth(2), Some((&30, &"c")));
+        assert!(lines.nth(5).is_none());
+        assert_eq!(lines.rank(&30), 2);
+        assert_eq!(lines.rank(&35), 3);
+        assert_eq!(lines.count_range(20..=40), 3);
+        assert_eq!(lines.count_range(..20), 1);
+        assert!(lines.iter_from(&25).map(|(k, _)| *k).eq([30, 40, 50]));
+        assert_eq!(lines.iter_from(&25).len(), 3);
+        assert_eq!(lines.min(), Some((&10, &"a")));
+        assert_eq!(lines.max(), Some((&50, &"e")));
+
+        let (value, rest) = lines.remove(&30).unwrap();
+        assert_eq!(*value, "c");
+        assert!(rest.keys().copied().eq([10, 20, 40, 50]));
+        assert!(lines.remove(&31).is_none());
+        let (key, _, rest) = lines.pop_min().unwrap();
+        assert_eq!((*key, rest.len()), (10, 4));
+        let (key, _, rest) = lines.pop_max().unwrap();
+        assert_eq!((*key, rest.len()), (50, 4));
+
+        let trimmed = lines.delete_range(20..40);
+        assert!(trimmed.keys().copied().eq([10, 40, 50]));
+        assert!(is_treap(&trimmed));
+        assert_eq!(lines.delete_range(60..).len(), 5);
+
+        let edited = lines
+            .update(&20, |v| v.map(|_| "B"))
+            .update(&25, |_| Some("new"))
+            .update(&50, |_| None)
+            .update(&99, |_| None);
+        assert!(edited.iter().map(|(k, v)| (*k, *v)).eq([
+            (10, "a"),
+            (20, "B"),
+            (25, "new"),
+            (30, "c"),
+            (40, "d")
+        ]));
+        assert!(is_treap(&edited));
+    }
+}
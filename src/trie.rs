@@ -1,100 +1,1059 @@
-use crate::RefCounter;
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, HashSet},
+    fmt::Debug,
+    mem, slice,
+};
+
+use crate::{aho_corasick::Matcher, list::List, RefCounter};
+
+// Radix trie: every edge is labelled with a non-empty run of elements, and
+// the labels leaving a node start with distinct elements
 pub struct Trie<T = u8, U = bool> {
     pub(crate) stored_value: Vec<RefCounter<U>>,
-    pub(crate) adjecent_nodes: Vec<(T, RefCounter<Trie<T, U>>)>,
+    pub(crate) adjecent_nodes: Vec<Edge<T, U>>,
+    // Values and distinct keys stored in this subtree
+    pub(crate) value_count: usize,
+    pub(crate) key_count: usize,
 }
 
-impl<T: Clone, U> Clone for Trie<T, U> {
+pub(crate) type Edge<T, U> = (RefCounter<[T]>, RefCounter<Trie<T, U>>);
+
+impl<T, U> Clone for Trie<T, U> {
     fn clone(&self) -> Self {
         Self {
             stored_value: self.stored_value.clone(),
             adjecent_nodes: self.adjecent_nodes.clone(),
+            value_count: self.value_count,
+            key_count: self.key_count,
+        }
+    }
+}
+
+// Frees the nodes with an explicit stack, so dropping a deep trie cannot
+// overflow the call stack. Nodes still shared with another trie are left
+// to their other owners
+impl<T, U> Drop for Trie<T, U> {
+    fn drop(&mut self) {
+        let mut stack: Vec<RefCounter<Trie<T, U>>> = mem::take(&mut self.adjecent_nodes)
+            .into_iter()
+            .map(|(_, child)| child)
+            .collect();
+        while let Option::Some(node) = stack.pop() {
+            if let Ok(mut node) = RefCounter::try_unwrap(node) {
+                stack.extend(
+                    mem::take(&mut node.adjecent_nodes)
+                        .into_iter()
+                        .map(|(_, child)| child),
+                );
+            }
         }
     }
 }
 
+// Prints as a map from each key to the values stored at it
+impl<T: Debug, U: Debug> Debug for Trie<T, U> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut entries = Vec::new();
+        self.debug_entries(&mut Vec::new(), &mut entries);
+        f.debug_map().entries(entries).finish()
+    }
+}
+
+impl<T, U> Trie<T, U> {
+    fn debug_entries<'a>(&'a self, key: &mut Vec<&'a T>, entries: &mut DebugEntries<'a, T, U>) {
+        if !self.stored_value.is_empty() {
+            let values: Vec<&U> = self.stored_value.iter().map(|v| v.as_ref()).collect();
+            entries.push((key.clone(), values));
+        }
+        for (label, child) in &self.adjecent_nodes {
+            let depth = key.len();
+            key.extend(label.iter());
+            child.debug_entries(key, entries);
+            key.truncate(depth);
+        }
+    }
+}
+
+type DebugEntries<'a, T, U> = Vec<(Vec<&'a T>, Vec<&'a U>)>;
+
+fn common_prefix_len<T: PartialEq>(a: &[T], b: &[T]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
 impl<T: PartialEq + Clone, U> Trie<T, U> {
     pub(crate) fn empty_store() -> Trie<T, U> {
         Trie {
             stored_value: Vec::new(),
             adjecent_nodes: Vec::new(),
+            value_count: 0,
+            key_count: 0,
         }
     }
     pub fn empty() -> Trie<T, U> {
         Trie {
             stored_value: Vec::new(),
             adjecent_nodes: Vec::new(),
+            value_count: 0,
+            key_count: 0,
         }
     }
     pub fn insert_store<Slc: AsRef<[T]>>(&self, value: Slc, store: U) -> Self {
-        let value_ref = value.as_ref();
+        self.update_values(value.as_ref(), |values| values.push(RefCounter::new(store)))
+    }
+    // Leaves store as the only value at the key
+    pub(crate) fn replace_store<Slc: AsRef<[T]>>(&self, value: Slc, store: U) -> Self {
+        self.update_values(value.as_ref(), |values| {
+            values.clear();
+            values.push(RefCounter::new(store));
+        })
+    }
+    // Copies the path to the key, creating it when missing, and applies the
+    // update to the values found at its end
+    fn update_values<F: FnOnce(&mut Vec<RefCounter<U>>)>(
+        &self,
+        value_ref: &[T],
+        update: F,
+    ) -> Self {
         let mut new_trie = self.clone();
         if value_ref.is_empty() {
-            new_trie.stored_value.push(RefCounter::new(store));
+            update(&mut new_trie.stored_value);
+            new_trie.refresh();
             return new_trie;
         }
-        let head = &value_ref[0];
-        let tail = &value_ref[1..];
-        for (k, v) in new_trie.adjecent_nodes.iter_mut() {
-            if k == head {
-                *v = RefCounter::new(v.insert_store(tail, store));
-                return new_trie;
+        for (label, child) in new_trie.adjecent_nodes.iter_mut() {
+            if label[0] != value_ref[0] {
+                continue;
             }
+            let common = common_prefix_len(label, value_ref);
+            if common == label.len() {
+                *child = RefCounter::new(child.update_values(&value_ref[common..], update));
+            } else {
+                // The key leaves the edge halfway, so the edge is split at that point
+                let mut middle = Trie::empty_store();
+                middle
+                    .adjecent_nodes
+                    .push((RefCounter::from(&label[common..]), child.clone()));
+                middle.refresh();
+                *child = RefCounter::new(middle.update_values(&value_ref[common..], update));
+                *label = RefCounter::from(&label[..common]);
+            }
+            new_trie.refresh();
+            return new_trie;
         }
         new_trie.adjecent_nodes.push((
-            head.clone(),
-            RefCounter::new(Trie::empty_store().insert_store(tail, store)),
+            RefCounter::from(value_ref),
+            RefCounter::new(Trie::empty_store().update_values(&[], update)),
         ));
+        new_trie.refresh();
+        new_trie
+    }
+    // Union of both tries. Keys present in both keep the values of self
+    // followed by the values of other, and subtrees found on one side only
+    // are shared as they are
+    pub fn merge(&self, other: &Self) -> Self {
+        self.merge_by(other, false, &mut |values, others| {
+            values.extend(others.iter().cloned())
+        })
+    }
+    // Merge where join decides the values of keys found on both sides. When
+    // joining a node with itself changes nothing, subtrees shared by both
+    // sides are kept without walking them
+    fn merge_by<F: FnMut(&mut Vec<RefCounter<U>>, &[RefCounter<U>])>(
+        &self,
+        other: &Self,
+        idempotent: bool,
+        join: &mut F,
+    ) -> Self {
+        if other.adjecent_nodes.is_empty() && other.stored_value.is_empty() {
+            return self.clone();
+        }
+        if self.adjecent_nodes.is_empty() && self.stored_value.is_empty() {
+            return other.clone();
+        }
+        let mut new_trie = self.clone();
+        if new_trie.stored_value.is_empty() {
+            new_trie
+                .stored_value
+                .extend(other.stored_value.iter().cloned());
+        } else if !other.stored_value.is_empty() {
+            join(&mut new_trie.stored_value, &other.stored_value);
+        }
+        for (other_label, other_child) in &other.adjecent_nodes {
+            let found = new_trie
+                .adjecent_nodes
+                .iter_mut()
+                .find(|(label, _)| label[0] == other_label[0]);
+            match found {
+                Option::Some((label, child)) => {
+                    if idempotent && label == other_label && RefCounter::ptr_eq(child, other_child)
+                    {
+                        continue;
+                    }
+                    let common = common_prefix_len(label, other_label);
+                    let left = Self::hang(&label[common..], child);
+                    let right = Self::hang(&other_label[common..], other_child);
+                    *child = RefCounter::new(left.merge_by(&right, idempotent, join));
+                    *label = RefCounter::from(&label[..common]);
+                }
+                Option::None => new_trie
+                    .adjecent_nodes
+                    .push((other_label.clone(), other_child.clone())),
+            }
+        }
+        new_trie.refresh();
         new_trie
     }
+    // Keys found on both sides, with the values of self
+    fn intersect(&self, other: &Self) -> Self {
+        let mut new_trie = Trie::empty_store();
+        if !other.stored_value.is_empty() {
+            new_trie.stored_value = self.stored_value.clone();
+        }
+        for (label, child) in &self.adjecent_nodes {
+            let Option::Some((other_label, other_child)) = other
+                .adjecent_nodes
+                .iter()
+                .find(|(other_label, _)| other_label[0] == label[0])
+            else {
+                continue;
+            };
+            if label == other_label && RefCounter::ptr_eq(child, other_child) {
+                new_trie.adjecent_nodes.push((label.clone(), child.clone()));
+                continue;
+            }
+            let common = common_prefix_len(label, other_label);
+            if common < label.len() && common < other_label.len() {
+                continue;
+            }
+            let left = Self::hang(&label[common..], child);
+            let right = Self::hang(&other_label[common..], other_child);
+            let prefix = RefCounter::from(&label[..common]);
+            if let Option::Some(edge) = Self::reattach(&prefix, left.intersect(&right)) {
+                new_trie.adjecent_nodes.push(edge);
+            }
+        }
+        new_trie.refresh();
+        new_trie
+    }
+    // Keys of self not found in other
+    fn subtract(&self, other: &Self) -> Self {
+        let mut new_trie = self.clone();
+        if !other.stored_value.is_empty() {
+            new_trie.stored_value.clear();
+        }
+        new_trie.adjecent_nodes.clear();
+        for (label, child) in &self.adjecent_nodes {
+            let found = other
+                .adjecent_nodes
+                .iter()
+                .find(|(other_label, _)| other_label[0] == label[0]);
+            let Option::Some((other_label, other_child)) = found else {
+                new_trie.adjecent_nodes.push((label.clone(), child.clone()));
+                continue;
+            };
+            if label == other_label && RefCounter::ptr_eq(child, other_child) {
+                continue;
+            }
+            let common = common_prefix_len(label, other_label);
+            if common < label.len() && common < other_label.len() {
+                new_trie.adjecent_nodes.push((label.clone(), child.clone()));
+                continue;
+            }
+            let left = Self::hang(&label[common..], child);
+            let right = Self::hang(&other_label[common..], other_child);
+            let prefix = RefCounter::from(&label[..common]);
+            if let Option::Some(edge) = Self::reattach(&prefix, left.subtract(&right)) {
+                new_trie.adjecent_nodes.push(edge);
+            }
+        }
+        new_trie.refresh();
+        new_trie
+    }
+    // The child as seen from the point where its edge is cut after the
+    // common part, which is the child itself when nothing of the label is left
+    fn hang(rest: &[T], child: &RefCounter<Trie<T, U>>) -> Trie<T, U> {
+        if rest.is_empty() {
+            return child.as_ref().clone();
+        }
+        let mut node = Trie::empty_store();
+        node.adjecent_nodes
+            .push((RefCounter::from(rest), child.clone()));
+        node.refresh();
+        node
+    }
+    // Number of stored values
+    pub fn len(&self) -> usize {
+        self.value_count
+    }
+    pub fn is_empty(&self) -> bool {
+        self.value_count == 0
+    }
+    // Number of distinct keys with at least one stored value
+    pub fn key_count(&self) -> usize {
+        self.key_count
+    }
     pub fn get_store<Slc: AsRef<[T]>>(&self, value: Slc) -> Option<Box<[&U]>> {
-        let value_ref = value.as_ref();
-        if value_ref.is_empty() {
-            let mut vr = Vec::new();
-            for v in self.stored_value.iter() {
-                vr.push(v.as_ref());
+        let node = self.node_at(value.as_ref())?;
+        if node.stored_value.is_empty() {
+            return Option::None;
+        }
+        Option::Some(node.stored_values())
+    }
+    // Length of the longest prefix of the query that has stored values, along
+    // with those values
+    pub fn longest_prefix<Slc: AsRef<[T]>>(&self, query: Slc) -> Option<(usize, Box<[&U]>)> {
+        let query = query.as_ref();
+        let mut node = self;
+        let mut depth = 0;
+        let mut best = Option::None;
+        loop {
+            if !node.stored_value.is_empty() {
+                best = Option::Some((depth, node));
+            }
+            match node.edge_along(&query[depth..]) {
+                Option::Some((label, child)) => {
+                    depth += label.len();
+                    node = child;
+                }
+                Option::None => break,
+            }
+        }
+        best.map(|(depth, node)| (depth, node.stored_values()))
+    }
+    // Everything stored under the prefix, with the prefix taken off the keys.
+    // The nodes below the prefix are shared with self
+    pub fn subtrie<Slc: AsRef<[T]>>(&self, prefix: Slc) -> Option<Self> {
+        let prefix = prefix.as_ref();
+        if prefix.is_empty() {
+            return if self.is_empty() {
+                Option::None
+            } else {
+                Option::Some(self.clone())
+            };
+        }
+        let (used, (label, child)) = self.edge_ending(prefix)?;
+        Option::Some(Self::hang(&label[used..], child))
+    }
+    // Whether some stored key starts with the prefix
+    pub fn contains_prefix<Slc: AsRef<[T]>>(&self, prefix: Slc) -> bool {
+        let prefix = prefix.as_ref();
+        if prefix.is_empty() {
+            return !self.is_empty();
+        }
+        self.edge_ending(prefix).is_some()
+    }
+    // Number of distinct stored keys starting with the prefix
+    pub fn count_prefix<Slc: AsRef<[T]>>(&self, prefix: Slc) -> usize {
+        let prefix = prefix.as_ref();
+        if prefix.is_empty() {
+            return self.key_count;
+        }
+        self.edge_ending(prefix)
+            .map_or(0, |(_, (_, child))| child.key_count)
+    }
+    // Automaton that finds every key inside a haystack in a single pass
+    pub fn build_matcher(&self) -> Matcher<T, U> {
+        Matcher::from_trie(self)
+    }
+    // Edge where a non-empty prefix runs out, along with how much of its
+    // label the prefix covers
+    fn edge_ending(&self, prefix: &[T]) -> Option<(usize, &Edge<T, U>)> {
+        let mut node = self;
+        let mut rest = prefix;
+        loop {
+            let edge = node
+                .adjecent_nodes
+                .iter()
+                .find(|(label, _)| label[0] == rest[0])?;
+            let common = common_prefix_len(&edge.0, rest);
+            if common == rest.len() {
+                return Option::Some((common, edge));
             }
-            if vr.is_empty() {
+            if common < edge.0.len() {
                 return Option::None;
             }
-            return Option::Some(vr.into_boxed_slice());
+            rest = &rest[common..];
+            node = &edge.1;
+        }
+    }
+    fn stored_values(&self) -> Box<[&U]> {
+        self.stored_value.iter().map(|v| v.as_ref()).collect()
+    }
+    // Node reached by following exactly the given key
+    fn node_at(&self, key: &[T]) -> Option<&Trie<T, U>> {
+        let mut node = self;
+        let mut rest = key;
+        while !rest.is_empty() {
+            let (label, child) = node.edge_along(rest)?;
+            rest = &rest[label.len()..];
+            node = child;
+        }
+        Option::Some(node)
+    }
+    // Edge whose whole label is a prefix of the key
+    fn edge_along(&self, key: &[T]) -> Option<&Edge<T, U>> {
+        self.adjecent_nodes
+            .iter()
+            .find(|(label, _)| key.starts_with(label))
+    }
+}
+
+impl<T: Clone, U> Trie<T, U> {
+    pub fn iter(&self) -> TrieIterator<'_, T, U> {
+        TrieIterator {
+            stack: vec![(Vec::new(), self)],
+            current: Option::None,
+            order: Option::None,
         }
-        let head = &value_ref[0];
-        let tail = &value_ref[1..];
-        for (k, v) in &self.adjecent_nodes {
-            if k == head {
-                return v.get_store(tail);
+    }
+    // Stored values in the order of iter, without building the keys
+    pub fn values(&self) -> impl Iterator<Item = &U> {
+        let mut stack = vec![self];
+        let mut current = [].iter();
+        std::iter::from_fn(move || loop {
+            if let Option::Some(value) = current.next() {
+                return Option::Some(RefCounter::as_ref(value));
+            }
+            let node = stack.pop()?;
+            stack.extend(
+                node.adjecent_nodes
+                    .iter()
+                    .rev()
+                    .map(|(_, child)| child.as_ref()),
+            );
+            current = node.stored_value.iter();
+        })
+    }
+    // Every key holding a value, once, in the order of iter
+    pub fn keys(&self) -> impl Iterator<Item = Vec<T>> + '_ {
+        let mut stack = vec![(Vec::new(), self)];
+        std::iter::from_fn(move || loop {
+            let (key, node) = stack.pop()?;
+            for (label, child) in node.adjecent_nodes.iter().rev() {
+                let mut child_key = key.clone();
+                child_key.extend_from_slice(label);
+                stack.push((child_key, child.as_ref()));
             }
+            if !node.stored_value.is_empty() {
+                return Option::Some(key);
+            }
+        })
+    }
+    // Same keys with every value rewritten. Edge labels are shared
+    pub fn map_values<W, F: FnMut(&U) -> W>(&self, mut f: F) -> Trie<T, W> {
+        self.map_with(&mut f)
+    }
+    fn map_with<W, F: FnMut(&U) -> W>(&self, f: &mut F) -> Trie<T, W> {
+        Trie {
+            stored_value: self
+                .stored_value
+                .iter()
+                .map(|v| RefCounter::new(f(v)))
+                .collect(),
+            adjecent_nodes: self
+                .adjecent_nodes
+                .iter()
+                .map(|(label, child)| (label.clone(), RefCounter::new(child.map_with(f))))
+                .collect(),
+            value_count: self.value_count,
+            key_count: self.key_count,
+        }
+    }
+}
+
+impl<T: Ord + Clone, U> Trie<T, U> {
+    // Keys in lexicographic order, so a key comes right before the keys it
+    // is a prefix of. Children are kept in insertion order, so every node
+    // visited sorts its edges
+    pub fn iter_sorted(&self) -> TrieIterator<'_, T, U> {
+        TrieIterator {
+            stack: vec![(Vec::new(), self)],
+            current: Option::None,
+            order: Option::Some(T::cmp),
+        }
+    }
+}
+
+// Depth-first walk that yields every stored value with the key leading to it
+pub struct TrieIterator<'a, T, U> {
+    stack: Vec<(Vec<T>, &'a Trie<T, U>)>,
+    current: Option<(Vec<T>, slice::Iter<'a, RefCounter<U>>)>,
+    // Order to visit the children in, when not the order they are stored in
+    order: Option<fn(&T, &T) -> Ordering>,
+}
+
+impl<'a, T: Clone, U> TrieIterator<'a, T, U> {
+    // Pushed last to first, so the first edge is popped first
+    fn push_children<I: DoubleEndedIterator<Item = &'a Edge<T, U>>>(
+        &mut self,
+        key: &[T],
+        edges: I,
+    ) {
+        for (label, child) in edges.rev() {
+            let mut child_key = key.to_vec();
+            child_key.extend_from_slice(label);
+            self.stack.push((child_key, child));
+        }
+    }
+}
+
+impl<'a, T: Clone, U> Iterator for TrieIterator<'a, T, U> {
+    type Item = (Vec<T>, &'a U);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Option::Some((key, values)) = &mut self.current {
+                if let Option::Some(value) = values.next() {
+                    return Option::Some((key.clone(), value.as_ref()));
+                }
+            }
+            let (key, node) = self.stack.pop()?;
+            match self.order {
+                Option::Some(order) => {
+                    let mut edges: Vec<&Edge<T, U>> = node.adjecent_nodes.iter().collect();
+                    edges.sort_by(|(a, _), (b, _)| order(&a[0], &b[0]));
+                    self.push_children(&key, edges.into_iter());
+                }
+                Option::None => self.push_children(&key, node.adjecent_nodes.iter()),
+            }
+            self.current = Option::Some((key, node.stored_value.iter()));
         }
-        return Option::None;
     }
 }
 
 impl<T: PartialEq + Clone, U: PartialEq> Trie<T, U> {
     pub fn delete_store<Slc: AsRef<[T]>>(&self, value: Slc, store: &U) -> Option<Self> {
-        let value_ref = value.as_ref();
+        self.remove_values(value.as_ref(), &mut |values| {
+            values.retain(|v| v.as_ref() != store)
+        })
+    }
+    // Removes the first value at the key equal to store, and hands it back
+    // along with the new trie
+    pub fn remove_store<Slc: AsRef<[T]>>(
+        &self,
+        value: Slc,
+        store: &U,
+    ) -> Option<(Self, RefCounter<U>)> {
+        let mut removed = Option::None;
+        let new_trie = self.remove_values(value.as_ref(), &mut |values| {
+            if let Option::Some(index) = values.iter().position(|v| v.as_ref() == store) {
+                removed = Option::Some(values.remove(index));
+            }
+        })?;
+        removed.map(|removed| (new_trie, removed))
+    }
+}
+
+impl<T: PartialEq + Clone, U> Trie<T, U> {
+    // Removes every value stored at the key
+    pub fn delete_key<Slc: AsRef<[T]>>(&self, key: Slc) -> Option<Self> {
+        self.remove_values(key.as_ref(), &mut |values| values.clear())
+    }
+    // Applies the removal to the values at the key. None when the key is not
+    // in the trie or nothing was removed
+    fn remove_values<F: FnMut(&mut Vec<RefCounter<U>>)>(
+        &self,
+        key: &[T],
+        remove: &mut F,
+    ) -> Option<Self> {
         let mut new_trie = self.clone();
-        if value_ref.is_empty() {
-            new_trie.stored_value.retain(|v| {
-                let retain = v.as_ref() != store;
-                retain
-            });
+        if key.is_empty() {
+            remove(&mut new_trie.stored_value);
             if self.stored_value.len() == new_trie.stored_value.len() {
                 return Option::None;
             } else {
+                new_trie.refresh();
                 return Option::Some(new_trie);
             }
         }
-        let head = &value_ref[0];
-        let tail = &value_ref[1..];
-        for (k, v) in new_trie.adjecent_nodes.iter_mut() {
-            if k == head {
-                let subt = v.delete_store(tail, store)?;
-                *v = RefCounter::new(subt);
+        for (index, (label, child)) in self.adjecent_nodes.iter().enumerate() {
+            if key.starts_with(label) {
+                let subt = child.remove_values(&key[label.len()..], remove)?;
+                match Self::reattach(label, subt) {
+                    Option::Some(edge) => new_trie.adjecent_nodes[index] = edge,
+                    Option::None => {
+                        new_trie.adjecent_nodes.remove(index);
+                    }
+                }
+                new_trie.refresh();
                 return Option::Some(new_trie);
             }
         }
-        return Option::None;
+        Option::None
+    }
+}
+
+impl<T: Clone, U> Trie<T, U> {
+    // Recomputes the counters after the values or the children changed
+    fn refresh(&mut self) {
+        self.value_count = self.stored_value.len();
+        self.key_count = usize::from(!self.stored_value.is_empty());
+        for (_, child) in &self.adjecent_nodes {
+            self.value_count += child.value_count;
+            self.key_count += child.key_count;
+        }
+    }
+    // Edge to put back after the child changed. Children left without values
+    // are dropped when they have no children either, or merged into their
+    // only child so the path stays compressed
+    fn reattach(label: &RefCounter<[T]>, mut child: Trie<T, U>) -> Option<Edge<T, U>> {
+        if !child.stored_value.is_empty() || child.adjecent_nodes.len() > 1 {
+            return Option::Some((label.clone(), RefCounter::new(child)));
+        }
+        let (child_label, grandchild) = child.adjecent_nodes.pop()?;
+        let merged: Vec<T> = label.iter().chain(child_label.iter()).cloned().collect();
+        Option::Some((RefCounter::from(merged), grandchild))
+    }
+}
+
+impl<T: PartialEq + Clone, U> Trie<T, U> {
+    // Every stored value whose key is within Levenshtein distance max_distance
+    // of the query, with that distance. Each element walked down adds one DP
+    // row, and a branch is left once no entry of its row is within reach
+    pub fn search_fuzzy<Slc: AsRef<[T]>>(
+        &self,
+        query: Slc,
+        max_distance: usize,
+    ) -> Vec<(Vec<T>, usize, &U)> {
+        let query = query.as_ref();
+        let first_row: Vec<usize> = (0..=query.len()).collect();
+        let mut found = Vec::new();
+        self.collect_fuzzy(query, max_distance, &first_row, &mut Vec::new(), &mut found);
+        found
+    }
+    fn collect_fuzzy<'a>(
+        &'a self,
+        query: &[T],
+        max_distance: usize,
+        row: &[usize],
+        key: &mut Vec<T>,
+        found: &mut Vec<(Vec<T>, usize, &'a U)>,
+    ) {
+        let distance = row[query.len()];
+        if distance <= max_distance {
+            found.extend(
+                self.stored_value
+                    .iter()
+                    .map(|v| (key.clone(), distance, v.as_ref())),
+            );
+        }
+        for (label, child) in &self.adjecent_nodes {
+            let mut current = row.to_vec();
+            let mut reachable = true;
+            for element in label.iter() {
+                current = Self::next_row(query, &current, element);
+                if current.iter().all(|&cost| cost > max_distance) {
+                    reachable = false;
+                    break;
+                }
+            }
+            if !reachable {
+                continue;
+            }
+            let depth = key.len();
+            key.extend_from_slice(label);
+            child.collect_fuzzy(query, max_distance, &current, key, found);
+            key.truncate(depth);
+        }
+    }
+    // Edit distances between the key extended by element and every prefix
+    // of the query
+    fn next_row(query: &[T], row: &[usize], element: &T) -> Vec<usize> {
+        let mut next = Vec::with_capacity(row.len());
+        next.push(row[0] + 1);
+        for (index, expected) in query.iter().enumerate() {
+            let replace = row[index] + usize::from(expected != element);
+            let insert = next[index] + 1;
+            let delete = row[index + 1] + 1;
+            next.push(replace.min(insert).min(delete));
+        }
+        next
+    }
+}
+
+impl<T: PartialEq + Clone, U> Trie<T, U> {
+    // The k completions of the prefix with the highest score, best first.
+    // Equal scores keep the order of iter. Only k candidates are held at a
+    // time, in a heap whose top is the weakest of them
+    pub fn top_completions<Slc: AsRef<[T]>, S: Ord, F: FnMut(&U) -> S>(
+        &self,
+        prefix: Slc,
+        k: usize,
+        mut score: F,
+    ) -> Vec<(Vec<T>, &U)> {
+        if k == 0 {
+            return Vec::new();
+        }
+        let mut best = BinaryHeap::with_capacity(k + 1);
+        for (order, (key, value)) in self.completions(prefix.as_ref()).enumerate() {
+            best.push(Reverse(Candidate {
+                score: score(value),
+                order,
+                key,
+                value,
+            }));
+            if best.len() > k {
+                best.pop();
+            }
+        }
+        best.into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(candidate)| (candidate.key, candidate.value))
+            .collect()
+    }
+    // Walk over the keys starting with the prefix
+    fn completions(&self, prefix: &[T]) -> TrieIterator<'_, T, U> {
+        let start = if prefix.is_empty() {
+            Option::Some((Vec::new(), self))
+        } else {
+            self.edge_ending(prefix).map(|(used, (label, child))| {
+                let mut key = prefix.to_vec();
+                key.extend_from_slice(&label[used..]);
+                (key, child.as_ref())
+            })
+        };
+        TrieIterator {
+            stack: start.into_iter().collect(),
+            current: Option::None,
+            order: Option::None,
+        }
+    }
+}
+
+// Ordered by score, and by position in the walk among equal scores so that
+// the earlier one counts as the greater
+struct Candidate<'a, S, T, U> {
+    score: S,
+    order: usize,
+    key: Vec<T>,
+    value: &'a U,
+}
+
+impl<S: Ord, T, U> Ord for Candidate<'_, S, T, U> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .cmp(&other.score)
+            .then_with(|| other.order.cmp(&self.order))
+    }
+}
+
+impl<S: Ord, T, U> PartialOrd for Candidate<'_, S, T, U> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Option::Some(self.cmp(other))
+    }
+}
+
+impl<S: Ord, T, U> PartialEq for Candidate<'_, S, T, U> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<S: Ord, T, U> Eq for Candidate<'_, S, T, U> {}
+
+impl<T: PartialEq, U> Trie<T, U> {
+    // Cursor at the root, before any element of a key
+    pub fn cursor(&self) -> TrieCursor<'_, T, U> {
+        TrieCursor {
+            at: Position {
+                node: self,
+                edge: Option::None,
+            },
+            path: List::empty(),
+        }
+    }
+}
+
+// Place in the trie reached by some sequence of elements. Each step down
+// only looks at the edges of one node, and the positions passed through are
+// kept in a persistent list, so stepping back up is cheap as well
+pub struct TrieCursor<'a, T, U> {
+    at: Position<'a, T, U>,
+    path: List<Position<'a, T, U>>,
+}
+
+// A node, or used elements into the label of one of its edges
+struct Position<'a, T, U> {
+    node: &'a Trie<T, U>,
+    edge: Option<(&'a Edge<T, U>, usize)>,
+}
+
+impl<T, U> Clone for Position<'_, T, U> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T, U> Copy for Position<'_, T, U> {}
+
+impl<T, U> Clone for TrieCursor<'_, T, U> {
+    fn clone(&self) -> Self {
+        TrieCursor {
+            at: self.at,
+            path: self.path.clone(),
+        }
+    }
+}
+
+impl<'a, T: PartialEq, U> TrieCursor<'a, T, U> {
+    // Cursor after one more element, or None when no key continues that way
+    pub fn descend(&self, element: &T) -> Option<Self> {
+        let (edge, used) = match self.at.edge {
+            Option::Some((edge, used)) => (edge, used),
+            Option::None => (
+                self.at
+                    .node
+                    .adjecent_nodes
+                    .iter()
+                    .find(|(label, _)| label[0] == *element)?,
+                0,
+            ),
+        };
+        if edge.0[used] != *element {
+            return Option::None;
+        }
+        let at = if used + 1 == edge.0.len() {
+            Position {
+                node: edge.1.as_ref(),
+                edge: Option::None,
+            }
+        } else {
+            Position {
+                node: self.at.node,
+                edge: Option::Some((edge, used + 1)),
+            }
+        };
+        Option::Some(TrieCursor {
+            at,
+            path: self.path.push_front(self.at),
+        })
+    }
+    // Cursor before the last element, or None at the root
+    pub fn ascend(&self) -> Option<Self> {
+        let (at, path) = self.path.pop_front()?;
+        Option::Some(TrieCursor { at: *at, path })
+    }
+    // Elements consumed since the root
+    pub fn depth(&self) -> usize {
+        self.path.length()
+    }
+    // Values stored at the key consumed so far
+    pub fn values(&self) -> impl Iterator<Item = &'a U> {
+        let stored: &'a [RefCounter<U>] = match self.at.edge {
+            Option::Some(_) => &[],
+            Option::None => &self.at.node.stored_value,
+        };
+        stored.iter().map(|value| value.as_ref())
+    }
+    // Whether some key is longer than the one consumed so far
+    pub fn can_descend(&self) -> bool {
+        self.at.edge.is_some() || !self.at.node.adjecent_nodes.is_empty()
+    }
+}
+
+// Shape of a trie, or of several tries counting the nodes they share once
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrieStats {
+    pub nodes: usize,
+    // Nodes on the longest path from the root, not counting the root
+    pub max_depth: usize,
+    // Children per node, over the nodes that have children
+    pub average_fan_out: f64,
+    // Estimate of the heap memory held by the nodes, labels and values,
+    // reference counts included
+    pub heap_bytes: usize,
+}
+
+impl<T, U> Trie<T, U> {
+    pub fn stats(&self) -> TrieStats {
+        Self::combined_stats([self])
+    }
+    // Stats of a group of tries, such as the snapshots kept of one trie
+    pub fn combined_stats<'a, I: IntoIterator<Item = &'a Self>>(tries: I) -> TrieStats
+    where
+        T: 'a,
+        U: 'a,
+    {
+        // Every allocation made through RefCounter also holds two counts
+        let counts = 2 * mem::size_of::<usize>();
+        let mut seen: HashSet<*const ()> = HashSet::new();
+        let mut stats = TrieStats {
+            nodes: 0,
+            max_depth: 0,
+            average_fan_out: 0.0,
+            heap_bytes: 0,
+        };
+        let (mut parents, mut edges) = (0, 0);
+        let mut stack: Vec<(&Trie<T, U>, usize)> = tries.into_iter().map(|t| (t, 0)).collect();
+        while let Option::Some((node, depth)) = stack.pop() {
+            stats.nodes += 1;
+            stats.max_depth = stats.max_depth.max(depth);
+            stats.heap_bytes += node.stored_value.capacity() * mem::size_of::<RefCounter<U>>()
+                + node.adjecent_nodes.capacity() * mem::size_of::<Edge<T, U>>();
+            for value in &node.stored_value {
+                if seen.insert(RefCounter::as_ptr(value) as *const ()) {
+                    stats.heap_bytes += mem::size_of::<U>() + counts;
+                }
+            }
+            if !node.adjecent_nodes.is_empty() {
+                parents += 1;
+                edges += node.adjecent_nodes.len();
+            }
+            for (label, child) in &node.adjecent_nodes {
+                if seen.insert(RefCounter::as_ptr(label) as *const ()) {
+                    stats.heap_bytes += mem::size_of_val(label.as_ref()) + counts;
+                }
+                if seen.insert(RefCounter::as_ptr(child) as *const ()) {
+                    stats.heap_bytes += mem::size_of::<Trie<T, U>>() + counts;
+                    stack.push((child, depth + 1));
+                }
+            }
+        }
+        if parents > 0 {
+            stats.average_fan_out = edges as f64 / parents as f64;
+        }
+        stats
+    }
+}
+
+// Element of a pattern given to match_pattern
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PatternItem<T> {
+    Exact(T),
+    // Any single element
+    Any,
+    // Any run of elements, including an empty one
+    AnyRun,
+}
+
+impl<T: PartialEq + Clone, U> Trie<T, U> {
+    // Every stored value whose key matches the pattern, in the order of iter.
+    // The walk keeps the set of pattern positions reachable so far and leaves
+    // a branch as soon as that set is empty
+    pub fn match_pattern(&self, pattern: &[PatternItem<T>]) -> Vec<(Vec<T>, &U)> {
+        let mut found = Vec::new();
+        let start = Self::skip_runs(pattern, vec![0]);
+        self.collect_matches(pattern, &start, &mut Vec::new(), &mut found);
+        found
+    }
+    fn collect_matches<'a>(
+        &'a self,
+        pattern: &[PatternItem<T>],
+        positions: &[usize],
+        key: &mut Vec<T>,
+        found: &mut Vec<(Vec<T>, &'a U)>,
+    ) {
+        if positions.contains(&pattern.len()) {
+            found.extend(self.stored_value.iter().map(|v| (key.clone(), v.as_ref())));
+        }
+        for (label, child) in &self.adjecent_nodes {
+            let mut next = positions.to_vec();
+            for element in label.iter() {
+                next = Self::advance(pattern, &next, element);
+                if next.is_empty() {
+                    break;
+                }
+            }
+            if next.is_empty() {
+                continue;
+            }
+            let depth = key.len();
+            key.extend_from_slice(label);
+            child.collect_matches(pattern, &next, key, found);
+            key.truncate(depth);
+        }
+    }
+    // Pattern positions reachable after reading one more element
+    fn advance(pattern: &[PatternItem<T>], positions: &[usize], element: &T) -> Vec<usize> {
+        let mut next = Vec::new();
+        for &position in positions {
+            match pattern.get(position) {
+                Option::Some(PatternItem::Exact(expected)) if expected == element => {
+                    next.push(position + 1)
+                }
+                Option::Some(PatternItem::Any) => next.push(position + 1),
+                Option::Some(PatternItem::AnyRun) => next.push(position),
+                _ => {}
+            }
+        }
+        Self::skip_runs(pattern, next)
+    }
+    // Adds the positions after runs, which may match nothing
+    fn skip_runs(pattern: &[PatternItem<T>], mut positions: Vec<usize>) -> Vec<usize> {
+        let mut index = 0;
+        while index < positions.len() {
+            let position = positions[index];
+            if pattern.get(position) == Option::Some(&PatternItem::AnyRun) {
+                positions.push(position + 1);
+            }
+            index += 1;
+        }
+        positions.sort_unstable();
+        positions.dedup();
+        positions
+    }
+}
+
+impl<T: PartialEq + Clone, U> Trie<T, U> {
+    // Builds the nodes bottom-up in one pass. Sorted input is the fast case,
+    // but any order works: it only needs keys with a common first element
+    // to come together, and edges that show up twice get merged
+    pub fn from_sorted<Slc: AsRef<[T]>, I: IntoIterator<Item = (Slc, U)>>(entries: I) -> Self {
+        Self::build(entries.into_iter().collect(), 0)
+    }
+    // Node for entries whose keys all agree on their first depth elements
+    fn build<Slc: AsRef<[T]>>(entries: Vec<(Slc, U)>, depth: usize) -> Self {
+        let mut node = Trie::empty_store();
+        let mut group = Vec::new();
+        for (key, value) in entries {
+            if key.as_ref().len() == depth {
+                node.stored_value.push(RefCounter::new(value));
+                continue;
+            }
+            let starts_group = group
+                .first()
+                .is_some_and(|(first, _): &(Slc, U)| first.as_ref()[depth] != key.as_ref()[depth]);
+            if starts_group {
+                node = node.attach(std::mem::take(&mut group), depth);
+            }
+            group.push((key, value));
+        }
+        if !group.is_empty() {
+            node = node.attach(group, depth);
+        }
+        node.refresh();
+        node
+    }
+    // Adds the edge for a group of entries sharing their element at depth
+    fn attach<Slc: AsRef<[T]>>(mut self, group: Vec<(Slc, U)>, depth: usize) -> Self {
+        let first = group[0].0.as_ref();
+        let label_len = group
+            .iter()
+            .map(|(key, _)| common_prefix_len(&first[depth..], &key.as_ref()[depth..]))
+            .min()
+            .unwrap_or(0);
+        let label: RefCounter<[T]> = RefCounter::from(&first[depth..depth + label_len]);
+        let child = RefCounter::new(Self::build(group, depth + label_len));
+        if self
+            .adjecent_nodes
+            .iter()
+            .all(|(existing, _)| existing[0] != label[0])
+        {
+            self.adjecent_nodes.push((label, child));
+            return self;
+        }
+        let mut single = Trie::empty_store();
+        single.adjecent_nodes.push((label, child));
+        single.refresh();
+        self.refresh();
+        self.merge(&single)
+    }
+}
+
+impl<T: PartialEq + Clone, U, Slc: AsRef<[T]>> FromIterator<(Slc, U)> for Trie<T, U> {
+    fn from_iter<I: IntoIterator<Item = (Slc, U)>>(iter: I) -> Self {
+        Trie::from_sorted(iter)
     }
 }
 
@@ -108,6 +1067,15 @@ impl<T: PartialEq + Copy> Trie<T> {
     pub fn delete<Slc: AsRef<[T]>>(&self, value: Slc) -> Option<Self> {
         self.delete_store(value, &true)
     }
+    pub fn union(&self, other: &Self) -> Self {
+        self.merge_by(other, true, &mut |_, _| {})
+    }
+    pub fn intersection(&self, other: &Self) -> Self {
+        self.intersect(other)
+    }
+    pub fn difference(&self, other: &Self) -> Self {
+        self.subtract(other)
+    }
 }
 
 #[cfg(test)]
@@ -229,6 +1197,583 @@ mod tests {
         assert!(t.delete("not_key").is_none());
     }
 
+    #[test]
+    fn test_trie_iter() {
+        let t = Trie::empty_store()
+            .insert_store("ab", 1)
+            .insert_store("a", 2)
+            .insert_store("ab", 3)
+            .insert_store("b", 4)
+            .insert_store("", 5);
+        let entries: Vec<_> = t
+            .iter()
+            .map(|(key, value)| (String::from_utf8(key).unwrap(), *value))
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                ("".to_string(), 5),
+                ("a".to_string(), 2),
+                ("ab".to_string(), 1),
+                ("ab".to_string(), 3),
+                ("b".to_string(), 4)
+            ]
+        );
+        assert!(Trie::<u8, i32>::empty().iter().next().is_none());
+    }
+
+    #[test]
+    fn test_trie_longest_prefix() {
+        let t = Trie::empty_store()
+            .insert_store("a", "root-a")
+            .insert_store("a.b.c", "abc")
+            .insert_store("a.b", "ab")
+            .insert_store("x", "x");
+        let (len, values) = t.longest_prefix("a.b.d").unwrap();
+        assert_eq!(len, 3);
+        assert_eq!(&*values, &[&"ab"]);
+        assert_eq!(t.longest_prefix("a.b.c").unwrap().0, 5);
+        assert_eq!(t.longest_prefix("a.").unwrap().0, 1);
+        assert!(t.longest_prefix("b").is_none());
+        assert!(t.longest_prefix("").is_none());
+        let with_root = t.insert_store("", "root");
+        assert_eq!(with_root.longest_prefix("zzz").unwrap().0, 0);
+    }
+
+    #[test]
+    fn test_trie_compresses_paths() {
+        let t = Trie::empty().insert("abcdef");
+        assert_eq!(t.adjecent_nodes.len(), 1);
+        assert_eq!(&*t.adjecent_nodes[0].0, b"abcdef");
+
+        let t = t.insert("abcxyz").insert("ab");
+        assert_eq!(t.adjecent_nodes.len(), 1);
+        let (label, middle) = &t.adjecent_nodes[0];
+        assert_eq!(&**label, b"ab");
+        assert_eq!(middle.adjecent_nodes.len(), 1);
+        let (label, fork) = &middle.adjecent_nodes[0];
+        assert_eq!(&**label, b"c");
+        let mut labels: Vec<_> = fork.adjecent_nodes.iter().map(|(l, _)| &**l).collect();
+        labels.sort();
+        assert_eq!(labels, vec![b"def", b"xyz"]);
+        for key in ["abcdef", "abcxyz", "ab"] {
+            assert!(t.search(key));
+        }
+        for key in ["a", "abc", "abcd", "abcdefg", "b"] {
+            assert!(!t.search(key));
+        }
+        let t = t.delete("abcxyz").unwrap();
+        assert!(t.search("abcdef") && t.search("ab") && !t.search("abcxyz"));
+    }
+
+    #[test]
+    fn test_trie_delete_prunes_branches() {
+        let t = Trie::empty().insert("abc").insert("abd");
+        let pruned = t.delete("abc").unwrap().delete("abd").unwrap();
+        assert!(pruned.adjecent_nodes.is_empty());
+
+        let merged = Trie::empty()
+            .insert("ab")
+            .insert("abc")
+            .delete("ab")
+            .unwrap();
+        assert_eq!(merged.adjecent_nodes.len(), 1);
+        assert_eq!(&*merged.adjecent_nodes[0].0, b"abc");
+        assert!(merged.search("abc"));
+
+        let kept = Trie::empty()
+            .insert("abc")
+            .insert("abd")
+            .insert("x")
+            .delete("abd")
+            .unwrap();
+        assert_eq!(kept.adjecent_nodes.len(), 2);
+        assert!(kept
+            .adjecent_nodes
+            .iter()
+            .any(|(label, _)| &**label == b"abc"));
+        assert!(kept.search("abc") && kept.search("x"));
+    }
+
+    #[test]
+    fn test_trie_delete_key() {
+        let t = Trie::empty_store()
+            .insert_store("key", 1)
+            .insert_store("key", 2)
+            .insert_store("keys", 3);
+        let cleared = t.delete_key("key").unwrap();
+        assert!(cleared.get_store("key").is_none());
+        assert_eq!(&*cleared.get_store("keys").unwrap(), &[&3]);
+        assert_eq!(t.get_store("key").unwrap().len(), 2);
+        assert!(cleared.delete_key("key").is_none());
+        assert!(t.delete_key("ke").is_none());
+        let empty = cleared.delete_key("keys").unwrap();
+        assert!(empty.adjecent_nodes.is_empty());
+    }
+
+    #[test]
+    fn test_trie_len() {
+        let t = Trie::empty_store()
+            .insert_store("a", 1)
+            .insert_store("ab", 2)
+            .insert_store("ab", 3)
+            .insert_store("", 4)
+            .insert_store("b", 5);
+        assert_eq!(t.len(), 5);
+        assert_eq!(t.key_count(), 4);
+        let t2 = t.delete_store("ab", &2).unwrap();
+        assert_eq!((t2.len(), t2.key_count()), (4, 4));
+        let t3 = t2.delete_key("ab").unwrap().delete_key("a").unwrap();
+        assert_eq!((t3.len(), t3.key_count()), (2, 2));
+        assert_eq!((t.len(), t.key_count()), (5, 4));
+        assert!(Trie::<u8, i32>::empty().is_empty());
+        assert!(!t3.is_empty());
+    }
+
+    #[test]
+    fn test_trie_merge() {
+        let left = Trie::empty_store()
+            .insert_store("abcdef", 1)
+            .insert_store("ab", 2)
+            .insert_store("x", 3);
+        let right = Trie::empty_store()
+            .insert_store("abcxyz", 4)
+            .insert_store("ab", 5)
+            .insert_store("abcd", 6)
+            .insert_store("y", 7);
+        let merged = left.merge(&right);
+        assert_eq!(&*merged.get_store("ab").unwrap(), &[&2, &5]);
+        assert_eq!(&*merged.get_store("abcdef").unwrap(), &[&1]);
+        assert_eq!(&*merged.get_store("abcd").unwrap(), &[&6]);
+        assert_eq!(&*merged.get_store("abcxyz").unwrap(), &[&4]);
+        assert!(merged.get_store("abc").is_none());
+        assert_eq!((merged.len(), merged.key_count()), (7, 6));
+        assert_eq!(left.len(), 3);
+
+        let (label, fork) = &merged.adjecent_nodes[0];
+        assert_eq!(&**label, b"ab");
+        assert_eq!(fork.adjecent_nodes.len(), 1);
+        assert_eq!(&*fork.adjecent_nodes[0].0, b"c");
+        let shared = merged
+            .adjecent_nodes
+            .iter()
+            .find(|(label, _)| &**label == b"x")
+            .unwrap();
+        assert!(RefCounter::ptr_eq(&shared.1, &left.adjecent_nodes[1].1));
+        assert_eq!(Trie::empty().merge(&left).len(), 3);
+    }
+
+    #[test]
+    fn test_trie_subtrie() {
+        let t = Trie::empty_store()
+            .insert_store("config.network.port", 1)
+            .insert_store("config.network.host", 2)
+            .insert_store("config.network", 3)
+            .insert_store("config.disk", 4);
+        let network = t.subtrie("config.network").unwrap();
+        assert_eq!(&*network.get_store("").unwrap(), &[&3]);
+        assert_eq!(&*network.get_store(".port").unwrap(), &[&1]);
+        assert!(network.get_store("config.network.port").is_none());
+        assert_eq!(network.len(), 3);
+
+        // The prefix ends halfway through an edge
+        let dotted = t.subtrie("config.net").unwrap();
+        assert_eq!(&*dotted.get_store("work.host").unwrap(), &[&2]);
+        assert_eq!(dotted.len(), 3);
+        let labels: Vec<_> = dotted.adjecent_nodes.iter().map(|(l, _)| &**l).collect();
+        assert_eq!(labels, vec![b"work"]);
+
+        assert_eq!(t.subtrie("").unwrap().len(), 4);
+        assert!(t.subtrie("config.networks").is_none());
+        assert!(t.subtrie("x").is_none());
+        assert!(Trie::<u8, i32>::empty().subtrie("").is_none());
+    }
+
+    #[test]
+    fn test_trie_contains_prefix() {
+        let t = Trie::empty().insert("network.port").insert("disk");
+        assert!(t.contains_prefix("net"));
+        assert!(t.contains_prefix("network.port"));
+        assert!(t.contains_prefix(""));
+        assert!(!t.contains_prefix("network.port.x"));
+        assert!(!t.contains_prefix("networks"));
+        assert!(!t.search("net"));
+        let emptied = t.delete("disk").unwrap().delete("network.port").unwrap();
+        assert!(!emptied.contains_prefix(""));
+        assert!(!emptied.contains_prefix("d"));
+    }
+
+    #[test]
+    fn test_trie_match_pattern() {
+        let route = |path: &str| -> Vec<String> { path.split('/').map(String::from).collect() };
+        let t = Trie::empty_store()
+            .insert_store(route("users/1/posts"), "posts of 1")
+            .insert_store(route("users/2/posts"), "posts of 2")
+            .insert_store(route("users/2/likes"), "likes of 2")
+            .insert_store(route("users/2/posts/9"), "post 9");
+        let exact = |s: &str| PatternItem::Exact(s.to_string());
+        let posts = [exact("users"), PatternItem::Any, exact("posts")];
+        let values: Vec<_> = t
+            .match_pattern(&posts)
+            .into_iter()
+            .map(|(_, v)| *v)
+            .collect();
+        assert_eq!(values, vec!["posts of 1", "posts of 2"]);
+
+        let under_two = [exact("users"), exact("2"), PatternItem::AnyRun];
+        let keys: Vec<_> = t
+            .match_pattern(&under_two)
+            .into_iter()
+            .map(|(k, _)| k.join("/"))
+            .collect();
+        assert_eq!(
+            keys,
+            vec!["users/2/posts", "users/2/posts/9", "users/2/likes"]
+        );
+
+        let words = Trie::empty().insert("cat").insert("cart").insert("cut");
+        let any_run = [
+            PatternItem::Exact(b'c'),
+            PatternItem::AnyRun,
+            PatternItem::AnyRun,
+            PatternItem::Exact(b't'),
+        ];
+        assert_eq!(words.match_pattern(&any_run).len(), 3);
+        let one = [
+            PatternItem::Exact(b'c'),
+            PatternItem::Any,
+            PatternItem::Exact(b't'),
+        ];
+        assert_eq!(words.match_pattern(&one).len(), 2);
+        assert!(words.match_pattern(&[]).is_empty());
+        assert_eq!(words.match_pattern(&[PatternItem::AnyRun]).len(), 3);
+    }
+
+    #[test]
+    fn test_trie_search_fuzzy() {
+        let t = ["hello", "help", "hell", "shell", "yellow", "world"]
+            .iter()
+            .fold(Trie::empty(), |t, word| t.insert(word));
+        let near = |query: &str, k| {
+            let mut words: Vec<_> = t
+                .search_fuzzy(query, k)
+                .into_iter()
+                .map(|(key, distance, _)| (String::from_utf8(key).unwrap(), distance))
+                .collect();
+            words.sort();
+            words
+        };
+        assert_eq!(near("hell", 0), vec![("hell".to_string(), 0)]);
+        assert_eq!(
+            near("hell", 1),
+            vec![
+                ("hell".to_string(), 0),
+                ("hello".to_string(), 1),
+                ("help".to_string(), 1),
+                ("shell".to_string(), 1)
+            ]
+        );
+        assert_eq!(near("yelow", 1), vec![("yellow".to_string(), 1)]);
+        assert_eq!(near("wrold", 2), vec![("world".to_string(), 2)]);
+        assert!(near("xyz", 2).is_empty());
+        assert_eq!(near("", 4).len(), 2);
+    }
+
+    #[test]
+    fn test_trie_top_completions() {
+        let t = Trie::empty_store()
+            .insert_store("car", 30)
+            .insert_store("cart", 5)
+            .insert_store("care", 50)
+            .insert_store("cat", 40)
+            .insert_store("carbon", 30)
+            .insert_store("dog", 100);
+        let top = |prefix: &str, k| -> Vec<(String, i32)> {
+            t.top_completions(prefix, k, |score| *score)
+                .into_iter()
+                .map(|(key, value)| (String::from_utf8(key).unwrap(), *value))
+                .collect()
+        };
+        assert_eq!(
+            top("car", 3),
+            vec![
+                ("care".to_string(), 50),
+                ("car".to_string(), 30),
+                ("carbon".to_string(), 30)
+            ]
+        );
+        assert_eq!(top("ca", 1), vec![("care".to_string(), 50)]);
+        assert_eq!(top("carb", 5), vec![("carbon".to_string(), 30)]);
+        assert_eq!(top("", 2).len(), 2);
+        assert_eq!(top("", 10).len(), 6);
+        assert!(top("cab", 3).is_empty());
+        assert!(top("car", 0).is_empty());
+        let ties: Vec<_> = t
+            .top_completions("ca", 2, |_| 0)
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(ties, vec![b"car".to_vec(), b"cart".to_vec()]);
+    }
+
+    #[test]
+    fn test_trie_from_sorted() {
+        let words = ["a", "ab", "abc", "abd", "b", "bcd", "bce"];
+        let built = Trie::from_sorted(words.iter().map(|word| (word, true)));
+        let inserted = words.iter().fold(Trie::empty(), |t, word| t.insert(word));
+        let keys = |t: &Trie| t.iter().map(|(key, _)| key).collect::<Vec<_>>();
+        assert_eq!(keys(&built), keys(&inserted));
+        assert_eq!((built.len(), built.key_count()), (7, 7));
+        let labels: Vec<_> = built.adjecent_nodes.iter().map(|(l, _)| &**l).collect();
+        assert_eq!(labels, vec![b"a", b"b"]);
+        let (_, b) = &built.adjecent_nodes[1];
+        assert_eq!(&*b.adjecent_nodes[0].0, b"c");
+
+        // Out of order input still ends up with one edge per first element
+        let t: Trie<u8, i32> = [("ab", 1), ("x", 2), ("a", 3), ("ab", 4), ("abc", 5)]
+            .into_iter()
+            .collect();
+        assert_eq!(&*t.get_store("ab").unwrap(), &[&1, &4]);
+        assert_eq!(&*t.get_store("a").unwrap(), &[&3]);
+        assert_eq!(&*t.get_store("abc").unwrap(), &[&5]);
+        assert_eq!(t.adjecent_nodes.len(), 2);
+        assert_eq!((t.len(), t.key_count()), (5, 4));
+        assert!(Trie::<u8, i32>::from_sorted(Vec::<(&str, i32)>::new()).is_empty());
+    }
+
+    #[test]
+    fn test_trie_map_values() {
+        let routes = Trie::empty_store()
+            .insert_store("/users", "list_users")
+            .insert_store("/users/new", "new_user")
+            .insert_store("/users", "users_fallback");
+        let resolved = routes.map_values(|name| name.len());
+        assert_eq!(&*resolved.get_store("/users").unwrap(), &[&10, &14]);
+        assert_eq!(&*resolved.get_store("/users/new").unwrap(), &[&8]);
+        assert_eq!((resolved.len(), resolved.key_count()), (3, 2));
+        assert!(RefCounter::ptr_eq(
+            &routes.adjecent_nodes[0].0,
+            &resolved.adjecent_nodes[0].0
+        ));
+        let mut seen = Vec::new();
+        routes.map_values(|name| seen.push(*name));
+        assert_eq!(seen, vec!["list_users", "users_fallback", "new_user"]);
+    }
+
+    #[test]
+    fn test_trie_debug() {
+        let t = Trie::empty_store()
+            .insert_store("ab", 1)
+            .insert_store("a", 2)
+            .insert_store("ab", 3);
+        assert_eq!(format!("{:?}", t), "{[97]: [2], [97, 98]: [1, 3]}");
+        let words = Trie::empty_store().insert_store(['h', 'i'], "greeting");
+        assert_eq!(format!("{:?}", words), r#"{['h', 'i']: ["greeting"]}"#);
+        assert_eq!(format!("{:?}", Trie::<u8, i32>::empty()), "{}");
+    }
+
+    #[test]
+    fn test_trie_values() {
+        let t = Trie::empty_store()
+            .insert_store("/users", 1)
+            .insert_store("/users/new", 2)
+            .insert_store("/posts", 3)
+            .insert_store("/users", 4);
+        assert_eq!(t.values().sum::<i32>(), 10);
+        let from_iter: Vec<_> = t.iter().map(|(_, value)| value).collect();
+        assert_eq!(t.values().collect::<Vec<_>>(), from_iter);
+        assert!(Trie::<u8, i32>::empty().values().next().is_none());
+    }
+
+    #[test]
+    fn test_trie_keys() {
+        let t = Trie::empty_store()
+            .insert_store("/users", 1)
+            .insert_store("/users/new", 2)
+            .insert_store("/posts", 3)
+            .insert_store("/users", 4)
+            .insert_store("", 5);
+        let keys: Vec<Vec<u8>> = t.keys().collect();
+        assert_eq!(keys.len(), t.key_count());
+        let mut from_iter: Vec<Vec<u8>> = t.iter().map(|(key, _)| key).collect();
+        from_iter.dedup();
+        assert_eq!(keys, from_iter);
+        assert_eq!(keys[0], b"");
+        assert!(Trie::<u8, i32>::empty().keys().next().is_none());
+    }
+
+    #[test]
+    fn test_trie_remove_store() {
+        let t = Trie::empty_store()
+            .insert_store("conn", 1)
+            .insert_store("conn", 2)
+            .insert_store("conn", 1);
+        let (t2, removed) = t.remove_store("conn", &1).unwrap();
+        assert_eq!(*removed, 1);
+        assert_eq!(&*t2.get_store("conn").unwrap(), &[&2, &1]);
+        assert_eq!(t2.len(), 2);
+        assert!(RefCounter::ptr_eq(
+            &removed,
+            &t.node_at(b"conn").unwrap().stored_value[0]
+        ));
+        assert!(t2.remove_store("conn", &3).is_none());
+        assert!(t2.remove_store("con", &2).is_none());
+        let (t3, _) = t2.remove_store("conn", &2).unwrap();
+        let (t4, _) = t3.remove_store("conn", &1).unwrap();
+        assert!(t4.is_empty());
+        assert_eq!(t.len(), 3);
+    }
+
+    #[test]
+    fn test_trie_count_prefix() {
+        let t = Trie::empty_store()
+            .insert_store("car", 1)
+            .insert_store("car", 2)
+            .insert_store("cart", 3)
+            .insert_store("care", 4)
+            .insert_store("dog", 5);
+        assert_eq!(t.count_prefix("car"), 3);
+        assert_eq!(t.count_prefix("ca"), 3);
+        assert_eq!(t.count_prefix("cart"), 1);
+        assert_eq!(t.count_prefix("carts"), 0);
+        assert_eq!(t.count_prefix("x"), 0);
+        assert_eq!(t.count_prefix(""), 4);
+        let t = t.delete_key("cart").unwrap();
+        assert_eq!(t.count_prefix("car"), 2);
+    }
+
+    #[test]
+    fn test_trie_set_operations() {
+        let shared = Trie::empty().insert("a/x").insert("a/y");
+        let crawl1 = shared.insert("b").insert("abc").insert("c/1");
+        let crawl2 = shared.insert("b/2").insert("ab").insert("c/1");
+        let keys = |t: &Trie| {
+            let mut keys: Vec<_> = t
+                .iter()
+                .map(|(key, _)| String::from_utf8(key).unwrap())
+                .collect();
+            keys.sort();
+            keys
+        };
+        assert_eq!(
+            keys(&crawl1.union(&crawl2)),
+            vec!["a/x", "a/y", "ab", "abc", "b", "b/2", "c/1"]
+        );
+        assert_eq!(crawl1.union(&crawl2).len(), 7);
+        assert_eq!(
+            keys(&crawl1.intersection(&crawl2)),
+            vec!["a/x", "a/y", "c/1"]
+        );
+        assert_eq!(keys(&crawl1.difference(&crawl2)), vec!["abc", "b"]);
+        assert_eq!(keys(&crawl2.difference(&crawl1)), vec!["ab", "b/2"]);
+        assert!(crawl1.difference(&crawl1).is_empty());
+        assert_eq!(crawl1.intersection(&Trie::empty()).len(), 0);
+
+        // The difference of "abc" and "ab" leaves a compressed edge behind
+        let single = Trie::empty().insert("abc").insert("ab");
+        let rest = single.difference(&Trie::empty().insert("ab"));
+        assert_eq!(rest.adjecent_nodes.len(), 1);
+        assert_eq!(&*rest.adjecent_nodes[0].0, b"abc");
+
+        let union = crawl1.union(&crawl1);
+        assert_eq!(union.len(), crawl1.len());
+    }
+
+    #[test]
+    fn test_trie_iter_sorted() {
+        let words = ["pear", "apple", "peach", "app", "banana", "pea", "b"];
+        let t = words.iter().fold(Trie::empty(), |t, word| t.insert(word));
+        let sorted: Vec<_> = t
+            .iter_sorted()
+            .map(|(key, _)| String::from_utf8(key).unwrap())
+            .collect();
+        let mut expected: Vec<_> = words.iter().map(|word| word.to_string()).collect();
+        expected.sort();
+        assert_eq!(sorted, expected);
+        let unsorted: Vec<_> = t.iter().map(|(key, _)| key).collect();
+        assert_eq!(unsorted[0], b"pea");
+        let merged = Trie::empty()
+            .insert("pear")
+            .insert("b")
+            .union(&Trie::empty().insert("apple").insert("banana"))
+            .union(&Trie::empty().insert("peach").insert("app").insert("pea"));
+        let sorted: Vec<_> = merged
+            .iter_sorted()
+            .map(|(key, _)| String::from_utf8(key).unwrap())
+            .collect();
+        assert_eq!(sorted, expected);
+    }
+
+    #[test]
+    fn test_trie_cursor() {
+        let t = Trie::empty_store()
+            .insert_store("if", "keyword")
+            .insert_store("iff", "logic")
+            .insert_store("in", "keyword")
+            .insert_store("int", "type");
+        let root = t.cursor();
+        let i = root.descend(&b'i').unwrap();
+        assert_eq!(i.values().count(), 0);
+        assert!(i.descend(&b'x').is_none());
+        let int = i.descend(&b'n').unwrap().descend(&b't').unwrap();
+        assert_eq!(int.values().collect::<Vec<_>>(), vec![&"type"]);
+        assert_eq!(int.depth(), 3);
+        assert!(!int.can_descend());
+        let back = int.ascend().unwrap();
+        assert_eq!(back.values().collect::<Vec<_>>(), vec![&"keyword"]);
+        assert!(back.ascend().unwrap().ascend().unwrap().ascend().is_none());
+
+        // Stepping through a compressed edge one element at a time
+        let long = Trie::empty().insert("abcd");
+        let mut cursor = long.cursor();
+        for element in b"abc" {
+            cursor = cursor.descend(element).unwrap();
+            assert_eq!(cursor.values().count(), 0);
+            assert!(cursor.can_descend());
+        }
+        assert!(cursor.descend(&b'x').is_none());
+        let end = cursor.descend(&b'd').unwrap();
+        assert_eq!(end.values().count(), 1);
+        assert_eq!(end.ascend().unwrap().depth(), 3);
+    }
+
+    #[test]
+    fn test_trie_stats() {
+        let t = Trie::empty().insert("abc").insert("abd").insert("x");
+        let stats = t.stats();
+        assert_eq!(stats.nodes, 5);
+        assert_eq!(stats.max_depth, 2);
+        assert_eq!(stats.average_fan_out, 2.0);
+        assert!(stats.heap_bytes > 0);
+        assert_eq!(Trie::<u8, bool>::empty().stats().nodes, 1);
+
+        // A snapshot only adds the nodes on the path that was copied
+        let next = t.insert("xyz");
+        let both = Trie::combined_stats([&t, &next]);
+        assert_eq!(both.nodes, t.stats().nodes + 3);
+        assert!(both.heap_bytes < t.stats().heap_bytes + next.stats().heap_bytes);
+    }
+
+    #[test]
+    fn test_trie_drop_deep() {
+        // Every prefix holds a value, so no edge can be compressed
+        let depth = 100_000;
+        let mut t: Trie = Trie::empty().insert("a");
+        for _ in 1..depth {
+            let mut parent = Trie::empty().insert("");
+            parent
+                .adjecent_nodes
+                .push((RefCounter::from(&b"a"[..]), RefCounter::new(t)));
+            parent.refresh();
+            t = parent;
+        }
+        assert_eq!(t.stats().max_depth, depth);
+        let snapshot = t.clone();
+        drop(t);
+        assert!(snapshot.search(vec![b'a'; depth]));
+        drop(snapshot);
+    }
+
     #[test]
     fn test_readme() {
         // Insert words
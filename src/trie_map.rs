@@ -0,0 +1,114 @@
+use std::fmt::Debug;
+
+use crate::trie::{Trie, TrieIterator};
+
+// Map on top of a Trie that keeps a single value per key: putting a key that
+// is already there replaces its value
+pub struct TrieMap<T = u8, U = bool> {
+    trie: Trie<T, U>,
+}
+
+impl<T, U> Clone for TrieMap<T, U> {
+    fn clone(&self) -> Self {
+        Self {
+            trie: self.trie.clone(),
+        }
+    }
+}
+
+impl<T: PartialEq + Clone, U> Default for TrieMap<T, U> {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<T: Debug + Clone, U: Debug> Debug for TrieMap<T, U> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.trie.iter()).finish()
+    }
+}
+
+impl<T: PartialEq + Clone, U> TrieMap<T, U> {
+    pub fn empty() -> Self {
+        Self {
+            trie: Trie::empty(),
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.trie.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.trie.is_empty()
+    }
+    pub fn as_trie(&self) -> &Trie<T, U> {
+        &self.trie
+    }
+    pub fn put<Slc: AsRef<[T]>>(&self, key: Slc, value: U) -> Self {
+        Self {
+            trie: self.trie.replace_store(key, value),
+        }
+    }
+    pub fn get<Slc: AsRef<[T]>>(&self, key: Slc) -> Option<&U> {
+        self.trie.get_store(key).map(|values| values[0])
+    }
+    pub fn contains_key<Slc: AsRef<[T]>>(&self, key: Slc) -> bool {
+        self.get(key).is_some()
+    }
+    pub fn delete<Slc: AsRef<[T]>>(&self, key: Slc) -> Option<Self> {
+        let trie = self.trie.delete_key(key)?;
+        Some(Self { trie })
+    }
+    pub fn contains_prefix<Slc: AsRef<[T]>>(&self, prefix: Slc) -> bool {
+        self.trie.contains_prefix(prefix)
+    }
+    pub fn iter(&self) -> TrieIterator<'_, T, U> {
+        self.trie.iter()
+    }
+    pub fn keys(&self) -> impl Iterator<Item = Vec<T>> + '_ {
+        self.trie.keys()
+    }
+    pub fn values(&self) -> impl Iterator<Item = &U> {
+        self.trie.values()
+    }
+}
+
+impl<T: PartialEq + Clone, U, Slc: AsRef<[T]>> FromIterator<(Slc, U)> for TrieMap<T, U> {
+    fn from_iter<I: IntoIterator<Item = (Slc, U)>>(iter: I) -> Self {
+        iter.into_iter()
+            .fold(TrieMap::empty(), |map, (key, value)| map.put(key, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trie_map_replaces_values() {
+        let m = TrieMap::empty()
+            .put("host", "localhost")
+            .put("port", "80")
+            .put("host", "example.com");
+        assert_eq!(m.get("host"), Some(&"example.com"));
+        assert_eq!(m.get("port"), Some(&"80"));
+        assert!(m.get("hos").is_none());
+        assert_eq!(m.len(), 2);
+
+        let older = m.put("port", "8080");
+        assert_eq!(older.get("port"), Some(&"8080"));
+        assert_eq!(m.get("port"), Some(&"80"));
+
+        let removed = m.delete("host").unwrap();
+        assert!(!removed.contains_key("host"));
+        assert!(removed.delete("host").is_none());
+        assert_eq!(removed.len(), 1);
+    }
+
+    #[test]
+    fn test_trie_map_from_iter() {
+        let m: TrieMap<u8, i32> = [("a", 1), ("b", 2), ("a", 3)].into_iter().collect();
+        assert_eq!(m.len(), 2);
+        assert_eq!(format!("{:?}", m), "{[97]: 3, [98]: 2}");
+        assert_eq!(m.values().sum::<i32>(), 5);
+    }
+}
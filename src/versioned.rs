@@ -0,0 +1,158 @@
+use std::borrow::Borrow;
+
+use crate::avl::{Compare, Natural, AVL};
+
+pub type Version = u64;
+
+// Every key keeps the history of the values it held, indexed by the version
+// that wrote them. A deletion is recorded as a None entry
+pub struct VersionedMap<K, V, C = Natural> {
+    entries: AVL<K, AVL<Version, Option<V>>, C>,
+    version: Version,
+    len: usize,
+}
+
+impl<K, V, C> Clone for VersionedMap<K, V, C> {
+    fn clone(&self) -> Self {
+        Self {
+            entries: self.entries.clone(),
+            version: self.version,
+            len: self.len,
+        }
+    }
+}
+
+impl<K: Ord, V> VersionedMap<K, V> {
+    pub fn empty() -> Self {
+        Self {
+            entries: AVL::empty(),
+            version: 0,
+            len: 0,
+        }
+    }
+}
+
+impl<K, V, C: Compare<K>> VersionedMap<K, V, C> {
+    // Version of the latest write, 0 before the first one
+    pub fn version(&self) -> Version {
+        self.version
+    }
+    // Keys present at the latest version
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    pub fn put(&self, key: K, value: V) -> Self {
+        let was_present = self.get(&key).is_some();
+        self.record(key, Some(value), self.len + usize::from(!was_present))
+    }
+    pub fn delete(&self, key: &K) -> Self
+    where
+        K: Clone,
+    {
+        match self.get(key) {
+            Some(_) => self.record(key.clone(), None, self.len - 1),
+            None => self.clone(),
+        }
+    }
+    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.get_at(key, self.version)
+    }
+    // Value the key held right after the given version was written
+    pub fn get_at<Q: ?Sized>(&self, key: &Q, version: Version) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        Self::value_at(self.entries.find(key)?, version)
+    }
+    pub fn history<Q: ?Sized>(&self, key: &Q) -> impl Iterator<Item = (Version, Option<&V>)>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.entries
+            .find(key)
+            .into_iter()
+            .flat_map(|history| history.iter())
+            .map(|(version, value)| (*version, value.as_ref()))
+    }
+    pub fn iter_at(&self, version: Version) -> impl Iterator<Item = (&K, &V)> {
+        self.entries
+            .iter()
+            .filter_map(move |(key, history)| Some((key, Self::value_at(history, version)?)))
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.iter_at(self.version)
+    }
+
+    fn record(&self, key: K, value: Option<V>, len: usize) -> Self {
+        let version = self.version + 1;
+        let history = match self.entries.find(&key) {
+            Some(history) => history.put(version, value),
+            None => AVL::empty().put(version, value),
+        };
+        Self {
+            entries: self.entries.put(key, history),
+            version,
+            len,
+        }
+    }
+    fn value_at(history: &AVL<Version, Option<V>>, version: Version) -> Option<&V> {
+        match history.prev_before(&version.saturating_add(1)) {
+            Some((_, value)) => value.as_ref(),
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versioned_map_time_travel() {
+        let m = VersionedMap::empty()
+            .put("a", 1)
+            .put("b", 2)
+            .put("a", 3)
+            .delete(&"b")
+            .put("b", 4);
+        assert_eq!(m.version(), 5);
+        assert_eq!(m.len(), 2);
+        assert_eq!(m.get(&"a"), Some(&3));
+        assert_eq!(m.get_at(&"a", 0), None);
+        assert_eq!(m.get_at(&"a", 1), Some(&1));
+        assert_eq!(m.get_at(&"a", 2), Some(&1));
+        assert_eq!(m.get_at(&"a", 3), Some(&3));
+        assert_eq!(m.get_at(&"b", 3), Some(&2));
+        assert_eq!(m.get_at(&"b", 4), None);
+        assert_eq!(m.get_at(&"b", 5), Some(&4));
+        assert_eq!(m.get_at(&"c", 5), None);
+
+        let at_four: Vec<_> = m.iter_at(4).collect();
+        assert_eq!(at_four, vec![(&"a", &3)]);
+        let latest: Vec<_> = m.iter().collect();
+        assert_eq!(latest, vec![(&"a", &3), (&"b", &4)]);
+        let history: Vec<_> = m.history(&"b").collect();
+        assert_eq!(history, vec![(2, Some(&2)), (4, None), (5, Some(&4))]);
+    }
+
+    #[test]
+    fn test_versioned_map_len() {
+        let m = VersionedMap::empty().put(1, "x").put(1, "y");
+        assert_eq!(m.len(), 1);
+        let deleted = m.delete(&1);
+        assert!(deleted.is_empty());
+        assert_eq!(deleted.version(), 3);
+        let unchanged = deleted.delete(&1);
+        assert_eq!(unchanged.version(), 3);
+        assert_eq!(m.get(&1), Some(&"y"));
+    }
+}
@@ -0,0 +1,797 @@
+use std::{borrow::Borrow, cmp::Ordering};
+
+use crate::RefCounter;
+
+// Bounded balance tree: the size of a subtree is never more than DELTA times
+// the size of its sibling. Balancing only looks at sizes, which lets join,
+// split and the set operations work on subtrees of any shape
+pub enum WeightBalanced<K, V = ()> {
+    Empty,
+    Node {
+        key: RefCounter<K>,
+        value: RefCounter<V>,
+        left: RefCounter<WeightBalanced<K, V>>,
+        right: RefCounter<WeightBalanced<K, V>>,
+        size: usize,
+    },
+}
+
+const DELTA: usize = 3;
+const RATIO: usize = 2;
+
+type Split<K, V> = (
+    WeightBalanced<K, V>,
+    Option<RefCounter<V>>,
+    WeightBalanced<K, V>,
+);
+type PoppedEntry<K, V> = (RefCounter<K>, RefCounter<V>, WeightBalanced<K, V>);
+
+impl<K, V> Clone for WeightBalanced<K, V> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Empty => Self::Empty,
+            Self::Node {
+                key,
+                value,
+                left,
+                right,
+                size,
+            } => Self::Node {
+                key: key.clone(),
+                value: value.clone(),
+                left: left.clone(),
+                right: right.clone(),
+                size: *size,
+            },
+        }
+    }
+}
+
+impl<K, V> WeightBalanced<K, V> {
+    fn node(
+        key: RefCounter<K>,
+        value: RefCounter<V>,
+        left: RefCounter<WeightBalanced<K, V>>,
+        right: RefCounter<WeightBalanced<K, V>>,
+    ) -> WeightBalanced<K, V> {
+        let size = 1 + left.len() + right.len();
+        WeightBalanced::Node {
+            key,
+            value,
+            left,
+            right,
+            size,
+        }
+    }
+    fn leaf(key: RefCounter<K>, value: RefCounter<V>) -> WeightBalanced<K, V> {
+        WeightBalanced::node(
+            key,
+            value,
+            RefCounter::new(WeightBalanced::Empty),
+            RefCounter::new(WeightBalanced::Empty),
+        )
+    }
+    pub fn len(&self) -> usize {
+        match self {
+            WeightBalanced::Empty => 0,
+            WeightBalanced::Node { size, .. } => *size,
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        matches!(self, WeightBalanced::Empty)
+    }
+    pub fn iter(&self) -> WeightBalancedIterator<'_, K, V> {
+        let mut iter = WeightBalancedIterator {
+            stack: Vec::new(),
+            remaining: self.len(),
+        };
+        iter.push_left_spine(self);
+        iter
+    }
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(key, _)| key)
+    }
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, value)| value)
+    }
+
+    // Rebuilds a node whose children were at most one insertion or deletion
+    // away from being balanced
+    fn balance(
+        key: RefCounter<K>,
+        value: RefCounter<V>,
+        left: RefCounter<WeightBalanced<K, V>>,
+        right: RefCounter<WeightBalanced<K, V>>,
+    ) -> WeightBalanced<K, V> {
+        let (left_size, right_size) = (left.len(), right.len());
+        if left_size + right_size <= 1 {
+            WeightBalanced::node(key, value, left, right)
+        } else if right_size > DELTA * left_size {
+            WeightBalanced::rotate_left(key, value, left, right)
+        } else if left_size > DELTA * right_size {
+            WeightBalanced::rotate_right(key, value, left, right)
+        } else {
+            WeightBalanced::node(key, value, left, right)
+        }
+    }
+    fn rotate_left(
+        key: RefCounter<K>,
+        value: RefCounter<V>,
+        left: RefCounter<WeightBalanced<K, V>>,
+        right: RefCounter<WeightBalanced<K, V>>,
+    ) -> WeightBalanced<K, V> {
+        if let WeightBalanced::Node {
+            key: rk,
+            value: rv,
+            left: rl,
+            right: rr,
+            ..
+        } = right.as_ref()
+        {
+            if rl.len() < RATIO * rr.len() {
+                return WeightBalanced::node(
+                    rk.clone(),
+                    rv.clone(),
+                    RefCounter::new(WeightBalanced::node(key, value, left, rl.clone())),
+                    rr.clone(),
+                );
+            }
+            if let WeightBalanced::Node {
+                key: rlk,
+                value: rlv,
+                left: rll,
+                right: rlr,
+                ..
+            } = rl.as_ref()
+            {
+                return WeightBalanced::node(
+                    rlk.clone(),
+                    rlv.clone(),
+                    RefCounter::new(WeightBalanced::node(key, value, left, rll.clone())),
+                    RefCounter::new(WeightBalanced::node(
+                        rk.clone(),
+                        rv.clone(),
+                        rlr.clone(),
+                        rr.clone(),
+                    )),
+                );
+            }
+        }
+        WeightBalanced::node(key, value, left, right)
+    }
+    fn rotate_right(
+        key: RefCounter<K>,
+        value: RefCounter<V>,
+        left: RefCounter<WeightBalanced<K, V>>,
+        right: RefCounter<WeightBalanced<K, V>>,
+    ) -> WeightBalanced<K, V> {
+        if let WeightBalanced::Node {
+            key: lk,
+            value: lv,
+            left: ll,
+            right: lr,
+            ..
+        } = left.as_ref()
+        {
+            if lr.len() < RATIO * ll.len() {
+                return WeightBalanced::node(
+                    lk.clone(),
+                    lv.clone(),
+                    ll.clone(),
+                    RefCounter::new(WeightBalanced::node(key, value, lr.clone(), right)),
+                );
+            }
+            if let WeightBalanced::Node {
+                key: lrk,
+                value: lrv,
+                left: lrl,
+                right: lrr,
+                ..
+            } = lr.as_ref()
+            {
+                return WeightBalanced::node(
+                    lrk.clone(),
+                    lrv.clone(),
+                    RefCounter::new(WeightBalanced::node(
+                        lk.clone(),
+                        lv.clone(),
+                        ll.clone(),
+                        lrl.clone(),
+                    )),
+                    RefCounter::new(WeightBalanced::node(key, value, lrr.clone(), right)),
+                );
+            }
+        }
+        WeightBalanced::node(key, value, left, right)
+    }
+    // Joins two trees and a key that sits between them
+    fn link(
+        left: &WeightBalanced<K, V>,
+        key: RefCounter<K>,
+        value: RefCounter<V>,
+        right: &WeightBalanced<K, V>,
+    ) -> WeightBalanced<K, V> {
+        match (left, right) {
+            (WeightBalanced::Empty, _) => right.insert_min(key, value),
+            (_, WeightBalanced::Empty) => left.insert_max(key, value),
+            (
+                WeightBalanced::Node {
+                    key: lk,
+                    value: lv,
+                    left: ll,
+                    right: lr,
+                    size: left_size,
+                },
+                WeightBalanced::Node {
+                    key: rk,
+                    value: rv,
+                    left: rl,
+                    right: rr,
+                    size: right_size,
+                },
+            ) => {
+                if DELTA * left_size < *right_size {
+                    WeightBalanced::balance(
+                        rk.clone(),
+                        rv.clone(),
+                        RefCounter::new(WeightBalanced::link(left, key, value, rl)),
+                        rr.clone(),
+                    )
+                } else if DELTA * right_size < *left_size {
+                    WeightBalanced::balance(
+                        lk.clone(),
+                        lv.clone(),
+                        ll.clone(),
+                        RefCounter::new(WeightBalanced::link(lr, key, value, right)),
+                    )
+                } else {
+                    WeightBalanced::node(
+                        key,
+                        value,
+                        RefCounter::new(left.clone()),
+                        RefCounter::new(right.clone()),
+                    )
+                }
+            }
+        }
+    }
+    // Joins two trees where every key of left is smaller than every key of right
+    fn link2(left: &WeightBalanced<K, V>, right: &WeightBalanced<K, V>) -> WeightBalanced<K, V> {
+        match (left, right) {
+            (WeightBalanced::Empty, _) => right.clone(),
+            (_, WeightBalanced::Empty) => left.clone(),
+            (
+                WeightBalanced::Node {
+                    key: lk,
+                    value: lv,
+                    left: ll,
+                    right: lr,
+                    size: left_size,
+                },
+                WeightBalanced::Node {
+                    key: rk,
+                    value: rv,
+                    left: rl,
+                    right: rr,
+                    size: right_size,
+                },
+            ) => {
+                if DELTA * left_size < *right_size {
+                    WeightBalanced::balance(
+                        rk.clone(),
+                        rv.clone(),
+                        RefCounter::new(WeightBalanced::link2(left, rl)),
+                        rr.clone(),
+                    )
+                } else if DELTA * right_size < *left_size {
+                    WeightBalanced::balance(
+                        lk.clone(),
+                        lv.clone(),
+                        ll.clone(),
+                        RefCounter::new(WeightBalanced::link2(lr, right)),
+                    )
+                } else {
+                    WeightBalanced::glue(left, right)
+                }
+            }
+        }
+    }
+    // Joins two trees whose sizes are already balanced against each other
+    fn glue(left: &WeightBalanced<K, V>, right: &WeightBalanced<K, V>) -> WeightBalanced<K, V> {
+        if left.len() > right.len() {
+            match left.pop_max() {
+                Some((key, value, rest)) => WeightBalanced::balance(
+                    key,
+                    value,
+                    RefCounter::new(rest),
+                    RefCounter::new(right.clone()),
+                ),
+                None => right.clone(),
+            }
+        } else {
+            match right.pop_min() {
+                Some((key, value, rest)) => WeightBalanced::balance(
+                    key,
+                    value,
+                    RefCounter::new(left.clone()),
+                    RefCounter::new(rest),
+                ),
+                None => left.clone(),
+            }
+        }
+    }
+    fn insert_min(&self, key: RefCounter<K>, value: RefCounter<V>) -> WeightBalanced<K, V> {
+        match self {
+            WeightBalanced::Empty => WeightBalanced::leaf(key, value),
+            WeightBalanced::Node {
+                key: k,
+                value: v,
+                left,
+                right,
+                ..
+            } => WeightBalanced::balance(
+                k.clone(),
+                v.clone(),
+                RefCounter::new(left.insert_min(key, value)),
+                right.clone(),
+            ),
+        }
+    }
+    fn insert_max(&self, key: RefCounter<K>, value: RefCounter<V>) -> WeightBalanced<K, V> {
+        match self {
+            WeightBalanced::Empty => WeightBalanced::leaf(key, value),
+            WeightBalanced::Node {
+                key: k,
+                value: v,
+                left,
+                right,
+                ..
+            } => WeightBalanced::balance(
+                k.clone(),
+                v.clone(),
+                left.clone(),
+                RefCounter::new(right.insert_max(key, value)),
+            ),
+        }
+    }
+    fn pop_min(&self) -> Option<PoppedEntry<K, V>> {
+        match self {
+            WeightBalanced::Empty => None,
+            WeightBalanced::Node {
+                key,
+                value,
+                left,
+                right,
+                ..
+            } => match left.pop_min() {
+                None => Some((key.clone(), value.clone(), right.as_ref().clone())),
+                Some((min_key, min_value, rest)) => Some((
+                    min_key,
+                    min_value,
+                    WeightBalanced::balance(
+                        key.clone(),
+                        value.clone(),
+                        RefCounter::new(rest),
+                        right.clone(),
+                    ),
+                )),
+            },
+        }
+    }
+    fn pop_max(&self) -> Option<PoppedEntry<K, V>> {
+        match self {
+            WeightBalanced::Empty => None,
+            WeightBalanced::Node {
+                key,
+                value,
+                left,
+                right,
+                ..
+            } => match right.pop_max() {
+                None => Some((key.clone(), value.clone(), left.as_ref().clone())),
+                Some((max_key, max_value, rest)) => Some((
+                    max_key,
+                    max_value,
+                    WeightBalanced::balance(
+                        key.clone(),
+                        value.clone(),
+                        left.clone(),
+                        RefCounter::new(rest),
+                    ),
+                )),
+            },
+        }
+    }
+}
+
+impl<K: Ord, V> WeightBalanced<K, V> {
+    pub fn empty() -> WeightBalanced<K, V> {
+        WeightBalanced::Empty
+    }
+    pub fn find<Q: ?Sized + Ord>(&self, target_key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+    {
+        let mut node = self;
+        while let WeightBalanced::Node {
+            key,
+            value,
+            left,
+            right,
+            ..
+        } = node
+        {
+            node = match target_key.cmp((**key).borrow()) {
+                Ordering::Less => left,
+                Ordering::Equal => return Some(value),
+                Ordering::Greater => right,
+            };
+        }
+        None
+    }
+    pub fn put(&self, key: K, value: V) -> WeightBalanced<K, V> {
+        self.put_rc(RefCounter::new(key), RefCounter::new(value))
+    }
+    fn put_rc(&self, key_rc: RefCounter<K>, value_rc: RefCounter<V>) -> WeightBalanced<K, V> {
+        match self {
+            WeightBalanced::Empty => WeightBalanced::leaf(key_rc, value_rc),
+            WeightBalanced::Node {
+                key,
+                value,
+                left,
+                right,
+                ..
+            } => match key_rc.as_ref().cmp(key) {
+                Ordering::Less => WeightBalanced::balance(
+                    key.clone(),
+                    value.clone(),
+                    RefCounter::new(left.put_rc(key_rc, value_rc)),
+                    right.clone(),
+                ),
+                Ordering::Equal => {
+                    WeightBalanced::node(key_rc, value_rc, left.clone(), right.clone())
+                }
+                Ordering::Greater => WeightBalanced::balance(
+                    key.clone(),
+                    value.clone(),
+                    left.clone(),
+                    RefCounter::new(right.put_rc(key_rc, value_rc)),
+                ),
+            },
+        }
+    }
+    pub fn delete<Q: ?Sized + Ord>(&self, target_key: &Q) -> WeightBalanced<K, V>
+    where
+        K: Borrow<Q>,
+    {
+        self.delete_node(target_key).unwrap_or_else(|| self.clone())
+    }
+    // None when the key is not in the tree
+    fn delete_node<Q: ?Sized + Ord>(&self, target_key: &Q) -> Option<WeightBalanced<K, V>>
+    where
+        K: Borrow<Q>,
+    {
+        match self {
+            WeightBalanced::Empty => None,
+            WeightBalanced::Node {
+                key,
+                value,
+                left,
+                right,
+                ..
+            } => match target_key.cmp((**key).borrow()) {
+                Ordering::Less => Some(WeightBalanced::balance(
+                    key.clone(),
+                    value.clone(),
+                    RefCounter::new(left.delete_node(target_key)?),
+                    right.clone(),
+                )),
+                Ordering::Equal => Some(WeightBalanced::glue(left, right)),
+                Ordering::Greater => Some(WeightBalanced::balance(
+                    key.clone(),
+                    value.clone(),
+                    left.clone(),
+                    RefCounter::new(right.delete_node(target_key)?),
+                )),
+            },
+        }
+    }
+    // Keys smaller than the bound go left, the rest go right
+    pub fn split<Q: ?Sized + Ord>(&self, bound: &Q) -> (WeightBalanced<K, V>, WeightBalanced<K, V>)
+    where
+        K: Borrow<Q>,
+    {
+        match self {
+            WeightBalanced::Empty => (WeightBalanced::Empty, WeightBalanced::Empty),
+            WeightBalanced::Node {
+                key,
+                value,
+                left,
+                right,
+                ..
+            } => {
+                if (**key).borrow() < bound {
+                    let (below, above) = right.split(bound);
+                    (
+                        WeightBalanced::link(left, key.clone(), value.clone(), &below),
+                        above,
+                    )
+                } else {
+                    let (below, above) = left.split(bound);
+                    (
+                        below,
+                        WeightBalanced::link(&above, key.clone(), value.clone(), right),
+                    )
+                }
+            }
+        }
+    }
+    fn split_three(&self, target_key: &K) -> Split<K, V> {
+        match self {
+            WeightBalanced::Empty => (WeightBalanced::Empty, None, WeightBalanced::Empty),
+            WeightBalanced::Node {
+                key,
+                value,
+                left,
+                right,
+                ..
+            } => match target_key.cmp(key) {
+                Ordering::Less => {
+                    let (less, found, greater) = left.split_three(target_key);
+                    let greater = WeightBalanced::link(&greater, key.clone(), value.clone(), right);
+                    (less, found, greater)
+                }
+                Ordering::Equal => (
+                    left.as_ref().clone(),
+                    Some(value.clone()),
+                    right.as_ref().clone(),
+                ),
+                Ordering::Greater => {
+                    let (less, found, greater) = right.split_three(target_key);
+                    let less = WeightBalanced::link(left, key.clone(), value.clone(), &less);
+                    (less, found, greater)
+                }
+            },
+        }
+    }
+    pub fn union<F: Fn(&V, &V) -> V>(
+        &self,
+        other: &WeightBalanced<K, V>,
+        merge: F,
+    ) -> WeightBalanced<K, V> {
+        self.union_with(other, &merge)
+    }
+    fn union_with<F: Fn(&V, &V) -> V>(
+        &self,
+        other: &WeightBalanced<K, V>,
+        merge: &F,
+    ) -> WeightBalanced<K, V> {
+        match (self, other) {
+            (_, WeightBalanced::Empty) => self.clone(),
+            (WeightBalanced::Empty, _) => other.clone(),
+            (
+                WeightBalanced::Node {
+                    key,
+                    value,
+                    left,
+                    right,
+                    ..
+                },
+                _,
+            ) => {
+                let (less, found, greater) = other.split_three(key);
+                let value = match found {
+                    Some(other_value) => RefCounter::new(merge(value, &other_value)),
+                    None => value.clone(),
+                };
+                WeightBalanced::link(
+                    &left.union_with(&less, merge),
+                    key.clone(),
+                    value,
+                    &right.union_with(&greater, merge),
+                )
+            }
+        }
+    }
+    pub fn intersection<F: Fn(&V, &V) -> V>(
+        &self,
+        other: &WeightBalanced<K, V>,
+        merge: F,
+    ) -> WeightBalanced<K, V> {
+        self.intersection_with(other, &merge)
+    }
+    fn intersection_with<F: Fn(&V, &V) -> V>(
+        &self,
+        other: &WeightBalanced<K, V>,
+        merge: &F,
+    ) -> WeightBalanced<K, V> {
+        match (self, other) {
+            (WeightBalanced::Empty, _) | (_, WeightBalanced::Empty) => WeightBalanced::Empty,
+            (
+                WeightBalanced::Node {
+                    key,
+                    value,
+                    left,
+                    right,
+                    ..
+                },
+                _,
+            ) => {
+                let (less, found, greater) = other.split_three(key);
+                let left = left.intersection_with(&less, merge);
+                let right = right.intersection_with(&greater, merge);
+                match found {
+                    Some(other_value) => WeightBalanced::link(
+                        &left,
+                        key.clone(),
+                        RefCounter::new(merge(value, &other_value)),
+                        &right,
+                    ),
+                    None => WeightBalanced::link2(&left, &right),
+                }
+            }
+        }
+    }
+    pub fn difference(&self, other: &WeightBalanced<K, V>) -> WeightBalanced<K, V> {
+        match (self, other) {
+            (WeightBalanced::Empty, _) => WeightBalanced::Empty,
+            (_, WeightBalanced::Empty) => self.clone(),
+            (
+                _,
+                WeightBalanced::Node {
+                    key, left, right, ..
+                },
+            ) => {
+                let (less, _, greater) = self.split_three(key);
+                WeightBalanced::link2(&less.difference(left), &greater.difference(right))
+            }
+        }
+    }
+}
+
+impl<K: Ord> WeightBalanced<K, ()> {
+    pub fn insert(&self, value: K) -> Self {
+        self.put(value, ())
+    }
+    pub fn search<Q: ?Sized + Ord>(&self, value: &Q) -> bool
+    where
+        K: Borrow<Q>,
+    {
+        self.find(value).is_some()
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for WeightBalanced<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        iter.into_iter()
+            .fold(WeightBalanced::empty(), |tree, (key, value)| {
+                tree.put(key, value)
+            })
+    }
+}
+
+pub struct WeightBalancedIterator<'a, K, V> {
+    stack: Vec<&'a WeightBalanced<K, V>>,
+    remaining: usize,
+}
+
+impl<'a, K, V> WeightBalancedIterator<'a, K, V> {
+    fn push_left_spine(&mut self, mut node: &'a WeightBalanced<K, V>) {
+        while let WeightBalanced::Node { left, .. } = node {
+            self.stack.push(node);
+            node = left;
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for WeightBalancedIterator<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.stack.pop()? {
+            WeightBalanced::Empty => None,
+            WeightBalanced::Node {
+                key, value, right, ..
+            } => {
+                self.remaining -= 1;
+                self.push_left_spine(right);
+                Some((key.as_ref(), value.as_ref()))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for WeightBalancedIterator<'a, K, V> {}
+
+impl<'a, K, V> IntoIterator for &'a WeightBalanced<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = WeightBalancedIterator<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_balanced<K: Ord, V>(t: &WeightBalanced<K, V>) -> bool {
+        match t {
+            WeightBalanced::Empty => true,
+            WeightBalanced::Node {
+                key,
+                left,
+                right,
+                size,
+                ..
+            } => {
+                let (left_size, right_size) = (left.len(), right.len());
+                let weights_ok = left_size + right_size <= 1
+                    || (left_size <= DELTA * right_size && right_size <= DELTA * left_size);
+                let keys_ok = left.keys().all(|k| k < key) && right.keys().all(|k| k > key);
+                weights_ok
+                    && keys_ok
+                    && *size == 1 + left_size + right_size
+                    && is_balanced(left)
+                    && is_balanced(right)
+            }
+        }
+    }
+
+    #[test]
+    fn test_weight_balanced_put_find_delete() {
+        let t: WeightBalanced<i32, i32> = (0..1000).map(|i| (i, i * 2)).collect();
+        assert_eq!(t.len(), 1000);
+        assert!(is_balanced(&t));
+        assert_eq!(t.find(&21), Some(&42));
+        assert!(t.find(&1000).is_none());
+        assert!(t.keys().copied().eq(0..1000));
+
+        let updated = t.put(21, 0);
+        assert_eq!(updated.find(&21), Some(&0));
+        assert_eq!(t.find(&21), Some(&42));
+
+        let removed = (0..1000).step_by(3).fold(t.clone(), |t, i| t.delete(&i));
+        assert_eq!(removed.len(), 666);
+        assert!(is_balanced(&removed));
+        assert!(removed.keys().all(|k| k % 3 != 0));
+        assert_eq!(removed.delete(&0).len(), 666);
+
+        let set = WeightBalanced::empty().insert("b").insert("a").insert("b");
+        assert_eq!(set.len(), 2);
+        assert!(set.search("a"));
+    }
+
+    #[test]
+    fn test_weight_balanced_set_operations() {
+        let evens: WeightBalanced<i32, i32> = (0..600).step_by(2).map(|i| (i, 1)).collect();
+        let small: WeightBalanced<i32, i32> = (0..100).map(|i| (i, 10)).collect();
+
+        let union = evens.union(&small, |a, b| a + b);
+        assert_eq!(union.len(), 350);
+        assert!(is_balanced(&union));
+        assert_eq!(union.find(&4), Some(&11));
+        assert_eq!(union.find(&5), Some(&10));
+        assert_eq!(union.find(&500), Some(&1));
+
+        let intersection = evens.intersection(&small, |a, b| a * b);
+        assert!(intersection.keys().copied().eq((0..100).step_by(2)));
+        assert!(intersection.values().all(|v| *v == 10));
+        assert!(is_balanced(&intersection));
+
+        let difference = evens.difference(&small);
+        assert!(difference.keys().copied().eq((100..600).step_by(2)));
+        assert!(is_balanced(&difference));
+
+        let (low, high) = union.split(&50);
+        assert!(low.keys().all(|k| *k < 50));
+        assert!(high.keys().all(|k| *k >= 50));
+        assert_eq!(low.len() + high.len(), union.len());
+        assert!(is_balanced(&low) && is_balanced(&high));
+    }
+}